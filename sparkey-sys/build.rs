@@ -19,5 +19,6 @@ fn main() {
         .file("sparkey/src/logwriter.c")
         .file("sparkey/src/returncodes.c")
         .file("sparkey/src/util.c")
+        .file("src/internal.c")
         .compile("libsparkey.a");
 }
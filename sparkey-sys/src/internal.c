@@ -0,0 +1,33 @@
+/*
+ * Accessors for iterator, reader and writer state that sparkey.h doesn't expose.
+ */
+#include "sparkey.h"
+#include "sparkey-internal.h"
+
+uint64_t sparkey_logiter_entry_block_position(sparkey_logiter *iter) {
+  return iter->entry_block_position;
+}
+
+int sparkey_logiter_entry_index(sparkey_logiter *iter) {
+  return iter->entry_count;
+}
+
+uint64_t sparkey_logreader_data_end(sparkey_logreader *log) {
+  return log->header.data_end;
+}
+
+int sparkey_logreader_fd(sparkey_logreader *log) {
+  return log->fd;
+}
+
+void sparkey_logreader_header(sparkey_logreader *log, sparkey_logheader *header) {
+  *header = log->header;
+}
+
+void sparkey_hashreader_header(sparkey_hashreader *reader, sparkey_hashheader *header) {
+  *header = reader->header;
+}
+
+int sparkey_logwriter_fd(sparkey_logwriter *log) {
+  return log->fd;
+}
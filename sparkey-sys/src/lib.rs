@@ -96,8 +96,61 @@ pub enum iter_state {
     ITER_CLOSED = 2,
     ITER_INVALID = 3,
 }
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct logheader {
+    pub major_version: uint32_t,
+    pub minor_version: uint32_t,
+    pub file_identifier: uint32_t,
+    pub num_puts: uint64_t,
+    pub num_deletes: uint64_t,
+    pub data_end: uint64_t,
+    pub max_key_len: uint64_t,
+    pub max_value_len: uint64_t,
+    pub delete_size: uint64_t,
+    pub compression_type: compression_type,
+    pub compression_block_size: uint32_t,
+    pub put_size: uint64_t,
+    pub header_size: uint32_t,
+    pub max_entries_per_block: uint32_t,
+}
 pub enum logreader {}
 pub enum logiter {}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct hash_algorithm {
+    pub hash: ::std::option::Option<
+        unsafe extern "C" fn(data: *const uint8_t, len: uint64_t, seed: uint32_t) -> uint64_t,
+    >,
+    pub read_hash: ::std::option::Option<
+        unsafe extern "C" fn(data: *const uint8_t, pos: uint64_t) -> uint64_t,
+    >,
+    pub write_hash: ::std::option::Option<unsafe extern "C" fn(data: *mut uint8_t, hash: uint64_t)>,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct hashheader {
+    pub major_version: uint32_t,
+    pub minor_version: uint32_t,
+    pub file_identifier: uint32_t,
+    pub hash_seed: uint32_t,
+    pub header_size: uint32_t,
+    pub data_end: uint64_t,
+    pub max_key_len: uint64_t,
+    pub max_value_len: uint64_t,
+    pub garbage_size: uint64_t,
+    pub num_entries: uint64_t,
+    pub address_size: uint32_t,
+    pub hash_size: uint32_t,
+    pub hash_capacity: uint64_t,
+    pub max_displacement: uint64_t,
+    pub num_puts: uint64_t,
+    pub entry_block_bits: uint32_t,
+    pub entry_block_bitmask: uint32_t,
+    pub hash_collisions: uint64_t,
+    pub total_displacement: uint64_t,
+    pub hash_algorithm: hash_algorithm,
+}
 pub enum hashreader {}
 extern "C" {
     #[link_name = "sparkey_errstring"]
@@ -132,6 +185,8 @@ extern "C" {
     pub fn logwriter_flush(log: *mut logwriter) -> returncode;
     #[link_name = "sparkey_logwriter_close"]
     pub fn logwriter_close(log: *mut *mut logwriter) -> returncode;
+    #[link_name = "sparkey_logwriter_fd"]
+    pub fn logwriter_fd(log: *mut logwriter) -> ::std::os::raw::c_int;
     #[link_name = "sparkey_logreader_open"]
     pub fn logreader_open(
         log: *mut *mut logreader,
@@ -147,6 +202,17 @@ extern "C" {
     pub fn logreader_get_compression_blocksize(log: *mut logreader) -> ::std::os::raw::c_int;
     #[link_name = "sparkey_logreader_get_compression_type"]
     pub fn logreader_get_compression_type(log: *mut logreader) -> compression_type;
+    #[link_name = "sparkey_load_logheader"]
+    pub fn load_logheader(
+        header: *mut logheader,
+        filename: *const ::std::os::raw::c_char,
+    ) -> returncode;
+    #[link_name = "sparkey_logreader_data_end"]
+    pub fn logreader_data_end(log: *mut logreader) -> uint64_t;
+    #[link_name = "sparkey_logreader_fd"]
+    pub fn logreader_fd(log: *mut logreader) -> ::std::os::raw::c_int;
+    #[link_name = "sparkey_logreader_header"]
+    pub fn logreader_header(log: *mut logreader, header: *mut logheader);
     #[link_name = "sparkey_logiter_create"]
     pub fn logiter_create(iter: *mut *mut logiter, log: *mut logreader) -> returncode;
     #[link_name = "sparkey_logiter_close"]
@@ -210,6 +276,10 @@ extern "C" {
     pub fn logiter_keylen(iter: *mut logiter) -> uint64_t;
     #[link_name = "sparkey_logiter_valuelen"]
     pub fn logiter_valuelen(iter: *mut logiter) -> uint64_t;
+    #[link_name = "sparkey_logiter_entry_block_position"]
+    pub fn logiter_entry_block_position(iter: *mut logiter) -> uint64_t;
+    #[link_name = "sparkey_logiter_entry_index"]
+    pub fn logiter_entry_index(iter: *mut logiter) -> ::std::os::raw::c_int;
     #[link_name = "sparkey_hash_write"]
     pub fn hash_write(
         hash_filename: *const ::std::os::raw::c_char,
@@ -222,6 +292,13 @@ extern "C" {
         hash_filename: *const ::std::os::raw::c_char,
         log_filename: *const ::std::os::raw::c_char,
     ) -> returncode;
+    #[link_name = "sparkey_load_hashheader"]
+    pub fn load_hashheader(
+        header: *mut hashheader,
+        filename: *const ::std::os::raw::c_char,
+    ) -> returncode;
+    #[link_name = "sparkey_hashreader_header"]
+    pub fn hashreader_header(reader: *mut hashreader, header: *mut hashheader);
     #[link_name = "sparkey_hash_getreader"]
     pub fn hash_getreader(reader: *mut hashreader) -> *mut logreader;
     #[link_name = "sparkey_hash_close"]
@@ -0,0 +1,41 @@
+//! Lookups that can be awaited from a tokio runtime.
+//!
+//! Sparkey reads are plain blocking reads of a memory-mapped file; `AsyncMap` only moves them
+//! onto tokio's blocking thread pool so they don't stall the runtime.  It is not true async IO.
+use std::io;
+use std::panic;
+use std::sync;
+
+use crate::error;
+use crate::hash;
+
+/// A `hash::Reader` shared with tokio's blocking pool.
+#[derive(Clone)]
+pub struct AsyncMap(sync::Arc<hash::Reader>);
+
+impl AsyncMap {
+    pub fn new(reader: hash::Reader) -> Self {
+        AsyncMap(sync::Arc::new(reader))
+    }
+
+    pub fn reader(&self) -> &hash::Reader {
+        &self.0
+    }
+
+    /// Looks up `key` on the blocking pool.  Must be called from within a tokio runtime.
+    pub async fn get(&self, key: Vec<u8>) -> error::Result<Option<bytes::BytesMut>> {
+        let reader = self.0.clone();
+
+        match tokio::task::spawn_blocking(move || reader.get(&key)).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(error::Error::IO(io::Error::other(e))),
+        }
+    }
+}
+
+impl From<sync::Arc<hash::Reader>> for AsyncMap {
+    fn from(reader: sync::Arc<hash::Reader>) -> Self {
+        AsyncMap(reader)
+    }
+}
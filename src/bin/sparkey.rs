@@ -295,8 +295,7 @@ fn run() -> Result<(), failure::Error> {
             debug!("raw value is {:?}", value);
 
             writer.put(&key, &value)?;
-            writer.flush()?;
-            drop(writer);
+            writer.close()?;
 
             if auto_index {
                 debug!("performing automatic index");
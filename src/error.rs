@@ -1,15 +1,48 @@
+use std::ffi;
 use std::io;
 use std::path;
 use std::result;
 
+use sparkey_sys::returncode;
+
+use crate::log;
+
+mod code;
+
+/// Implements `core::error::Error`, so the `core-error` feature needs Rust 1.81 or later.
+#[cfg(feature = "core-error")]
+pub use self::code::Code;
+
 pub type Result<A> = result::Result<A, Error>;
 
+/// Broad categories of errors, for handling related failures the same way.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorKind {
+    /// A file or directory that doesn't exist.
+    NotFound,
+    /// Any other failing system call.
+    Io,
+    /// A file that isn't a sparkey log or hash, or is from an unsupported version.
+    WrongType,
+    /// A file whose contents are inconsistent.
+    Corrupt,
+    /// A file or value that exceeds a size limit.
+    TooLarge,
+    /// An argument that can't be used, such as a bad path or block size.
+    InvalidInput,
+    /// An operation on a reader, writer or iterator that is closed or in the wrong state.
+    InvalidState,
+    /// A bug in sparkey.
+    Internal,
+}
+
 #[derive(Debug, failure::Fail)]
 pub enum Error {
     #[fail(display = "IO error")]
     IO(#[cause] io::Error),
     #[fail(display = "path not UTF-8: {:?}", path)]
     PathNotUTF8 { path: path::PathBuf },
+    /// The underlying `NulError` is available through `Fail::cause`.
     #[fail(
         display = "path contains null byte at position {}: {:?}",
         position, path
@@ -17,10 +50,14 @@ pub enum Error {
     PathContainsNul {
         path: path::PathBuf,
         position: usize,
+        #[cause]
+        cause: ffi::NulError,
     },
 
     #[fail(display = "internal error")]
     Internal,
+    #[fail(display = "null pointer")]
+    NullPointer,
     #[fail(display = "unexpected end-of-file")]
     UnexpectedEof,
     #[fail(display = "failed to mmap()")]
@@ -42,12 +79,67 @@ pub enum Error {
     LogIteratorMismatch,
     #[fail(display = "log iterator closed")]
     LogIteratorClosed,
+    #[fail(display = "log iterator has no hash index")]
+    NoHashIndex,
+    #[fail(display = "log reader was not opened from a path")]
+    NoPath,
+    #[fail(
+        display = "value reader ended after {} bytes, expected {}",
+        actual, expected
+    )]
+    ValueTooShort { expected: u64, actual: u64 },
+    #[fail(display = "value reader has more than the expected {} bytes", expected)]
+    ValueTooLong { expected: u64 },
+    #[fail(display = "{} of {} exceeds the maximum of {}", what, value, max)]
+    TooLarge {
+        what: &'static str,
+        value: u64,
+        max: u64,
+    },
+    #[fail(display = "failed to put pair {} of a batch: {}", index, error)]
+    BatchFailed { index: usize, error: Box<Error> },
+    #[fail(
+        display = "log is {} bytes long but its data ends at byte {}",
+        len, data_end
+    )]
+    LogTruncated { len: u64, data_end: u64 },
+    #[fail(display = "log entry {} is corrupt: {}", entry, error)]
+    LogEntryCorrupt { entry: u64, error: Box<Error> },
+    #[fail(display = "log header counts {} entries, found {}", expected, actual)]
+    LogEntryCountMismatch { expected: u64, actual: u64 },
+    #[fail(display = "{} is not valid UTF-8: {:?}", field, lossy)]
+    InvalidUtf8 { field: &'static str, lossy: String },
+    #[fail(display = "invalid record on line {}: {}", line, reason)]
+    InvalidRecord { line: u64, reason: String },
+    #[fail(display = "no entry at {}", offset)]
+    InvalidOffset { offset: log::Offset },
+    #[fail(display = "invalid framed value at byte {}: {}", position, reason)]
+    InvalidFrame {
+        position: usize,
+        reason: &'static str,
+    },
     #[fail(display = "log header corrupt")]
     LogHeaderCorrupt,
     #[fail(display = "invalid compression block size")]
     InvalidCompressionBlockSize,
+    #[fail(
+        display = "compression block size {} is too small for {} compression (minimum {})",
+        block_size, compression_type, minimum
+    )]
+    CompressionBlockSizeTooSmall {
+        compression_type: log::CompressionType,
+        block_size: u32,
+        minimum: u32,
+    },
     #[fail(display = "invalid compression type")]
     InvalidCompressionType,
+    #[fail(display = "values of compressed logs can't be mapped, use get instead")]
+    LogCompressed,
+    #[fail(display = "log uses {} compression, expected {}", actual, expected)]
+    CompressionTypeMismatch {
+        expected: log::CompressionType,
+        actual: log::CompressionType,
+    },
 
     #[fail(display = "wrong hash magic number")]
     WrongHashMagicNumber,
@@ -65,4 +157,147 @@ pub enum Error {
     HashHeaderCorrupt,
     #[fail(display = "hash size invalid")]
     HashSizeInvalid,
+    #[fail(
+        display = "stale index: it covers the log up to byte {}, but the log ends at byte {}",
+        index_data_end, log_data_end
+    )]
+    StaleIndex {
+        index_data_end: u64,
+        log_data_end: u64,
+    },
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        use self::Error::*;
+
+        match self {
+            IO(e) => match (e.kind(), e.raw_os_error()) {
+                (io::ErrorKind::NotFound, _) => ErrorKind::NotFound,
+                (_, Some(libc::EOVERFLOW)) | (_, Some(libc::EFBIG)) => ErrorKind::TooLarge,
+                _ => ErrorKind::Io,
+            },
+            MmapFailed => ErrorKind::Io,
+
+            WrongLogMagicNumber
+            | WrongLogMajorVersion
+            | UnsupportedLogMinorVersion
+            | WrongHashMagicNumber
+            | WrongHashMajorVersion
+            | UnsupportedHashMinorVersion
+            | FileIdentifierMismatch => ErrorKind::WrongType,
+
+            UnexpectedEof
+            | LogTooSmall
+            | LogTruncated { .. }
+            | LogEntryCorrupt { .. }
+            | LogEntryCountMismatch { .. }
+            | LogHeaderCorrupt
+            | HashTooSmall
+            | HashHeaderCorrupt
+            | StaleIndex { .. } => ErrorKind::Corrupt,
+
+            ValueTooLong { .. } | TooLarge { .. } => ErrorKind::TooLarge,
+
+            PathNotUTF8 { .. }
+            | PathContainsNul { .. }
+            | ValueTooShort { .. }
+            | InvalidCompressionBlockSize
+            | CompressionBlockSizeTooSmall { .. }
+            | InvalidCompressionType
+            | CompressionTypeMismatch { .. }
+            | InvalidRecord { .. }
+            | InvalidOffset { .. }
+            | InvalidFrame { .. }
+            | InvalidUtf8 { .. }
+            | LogCompressed
+            | NullPointer
+            | HashSizeInvalid => ErrorKind::InvalidInput,
+
+            LogClosed | LogIteratorInactive | LogIteratorMismatch | LogIteratorClosed
+            | NoHashIndex | NoPath | HashClosed => ErrorKind::InvalidState,
+
+            BatchFailed { error, .. } => error.kind(),
+
+            Internal => ErrorKind::Internal,
+        }
+    }
+
+    /// The return code this error stands for, if it is one a sparkey call can fail with.  IO
+    /// errors give `Code::Os` if they have an `errno` value.
+    #[cfg(feature = "core-error")]
+    pub fn code(&self) -> Option<Code> {
+        use self::Error::*;
+
+        Some(match self {
+            IO(e) => Code::Os(e.raw_os_error()?),
+            Internal => Code::Internal,
+            UnexpectedEof => Code::UnexpectedEof,
+            MmapFailed => Code::MmapFailed,
+
+            WrongLogMagicNumber => Code::WrongLogMagicNumber,
+            WrongLogMajorVersion => Code::WrongLogMajorVersion,
+            UnsupportedLogMinorVersion => Code::UnsupportedLogMinorVersion,
+            LogTooSmall => Code::LogTooSmall,
+            LogClosed => Code::LogClosed,
+            LogIteratorInactive => Code::LogIteratorInactive,
+            LogIteratorMismatch => Code::LogIteratorMismatch,
+            LogIteratorClosed => Code::LogIteratorClosed,
+            LogHeaderCorrupt => Code::LogHeaderCorrupt,
+            InvalidCompressionBlockSize => Code::InvalidCompressionBlockSize,
+            InvalidCompressionType => Code::InvalidCompressionType,
+
+            WrongHashMagicNumber => Code::WrongHashMagicNumber,
+            WrongHashMajorVersion => Code::WrongHashMajorVersion,
+            UnsupportedHashMinorVersion => Code::UnsupportedHashMinorVersion,
+            HashTooSmall => Code::HashTooSmall,
+            HashClosed => Code::HashClosed,
+            FileIdentifierMismatch => Code::FileIdentifierMismatch,
+            HashHeaderCorrupt => Code::HashHeaderCorrupt,
+            HashSizeInvalid => Code::HashSizeInvalid,
+
+            _ => return None,
+        })
+    }
+}
+
+/// Converts a sparkey return code; `SUCCESS` is not an error and converts to `Internal`.
+impl From<returncode> for Error {
+    fn from(returncode: returncode) -> Self {
+        code::Code::from_returncode(returncode).into()
+    }
+}
+
+impl From<code::Code> for Error {
+    fn from(code: code::Code) -> Self {
+        use self::code::Code::*;
+
+        match code {
+            Os(raw) => Error::IO(io::Error::from_raw_os_error(raw)),
+            Internal => Error::Internal,
+            UnexpectedEof => Error::UnexpectedEof,
+            MmapFailed => Error::MmapFailed,
+
+            WrongLogMagicNumber => Error::WrongLogMagicNumber,
+            WrongLogMajorVersion => Error::WrongLogMajorVersion,
+            UnsupportedLogMinorVersion => Error::UnsupportedLogMinorVersion,
+            LogTooSmall => Error::LogTooSmall,
+            LogClosed => Error::LogClosed,
+            LogIteratorInactive => Error::LogIteratorInactive,
+            LogIteratorMismatch => Error::LogIteratorMismatch,
+            LogIteratorClosed => Error::LogIteratorClosed,
+            LogHeaderCorrupt => Error::LogHeaderCorrupt,
+            InvalidCompressionBlockSize => Error::InvalidCompressionBlockSize,
+            InvalidCompressionType => Error::InvalidCompressionType,
+
+            WrongHashMagicNumber => Error::WrongHashMagicNumber,
+            WrongHashMajorVersion => Error::WrongHashMajorVersion,
+            UnsupportedHashMinorVersion => Error::UnsupportedHashMinorVersion,
+            HashTooSmall => Error::HashTooSmall,
+            HashClosed => Error::HashClosed,
+            FileIdentifierMismatch => Error::FileIdentifierMismatch,
+            HashHeaderCorrupt => Error::HashHeaderCorrupt,
+            HashSizeInvalid => Error::HashSizeInvalid,
+        }
+    }
 }
@@ -0,0 +1,160 @@
+//! The errors that sparkey's return codes stand for.
+//!
+//! Unlike `Error`, `Code` carries no IO errors, paths or boxed causes, so it is `Copy` and can be
+//! stored and compared freely.  The crate, including this module, still needs std.
+
+#[cfg(feature = "core-error")]
+use core::fmt;
+
+use sparkey_sys::returncode;
+
+#[cfg(feature = "core-error")]
+use super::ErrorKind;
+
+/// A sparkey return code other than success.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Code {
+    /// A failing system call, with its `errno` value.
+    Os(i32),
+    Internal,
+    UnexpectedEof,
+    MmapFailed,
+
+    WrongLogMagicNumber,
+    WrongLogMajorVersion,
+    UnsupportedLogMinorVersion,
+    LogTooSmall,
+    LogClosed,
+    LogIteratorInactive,
+    LogIteratorMismatch,
+    LogIteratorClosed,
+    LogHeaderCorrupt,
+    InvalidCompressionBlockSize,
+    InvalidCompressionType,
+
+    WrongHashMagicNumber,
+    WrongHashMajorVersion,
+    UnsupportedHashMinorVersion,
+    HashTooSmall,
+    HashClosed,
+    FileIdentifierMismatch,
+    HashHeaderCorrupt,
+    HashSizeInvalid,
+}
+
+impl Code {
+    /// Converts a sparkey return code; `SUCCESS` is not an error and converts to `Internal`.
+    pub fn from_returncode(returncode: returncode) -> Self {
+        use self::Code::*;
+        use sparkey_sys::returncode::*;
+
+        match returncode {
+            SUCCESS | INTERNAL_ERROR => Internal,
+
+            FILE_NOT_FOUND => Os(libc::ENOENT),
+            PERMISSION_DENIED => Os(libc::EACCES),
+            TOO_MANY_OPEN_FILES => Os(libc::ENFILE),
+            FILE_TOO_LARGE => Os(libc::EOVERFLOW),
+            FILE_ALREADY_EXISTS => Os(libc::EEXIST),
+            FILE_BUSY => Os(libc::EBUSY),
+            FILE_IS_DIRECTORY => Os(libc::EISDIR),
+            FILE_SIZE_EXCEEDED => Os(libc::EFBIG),
+            FILE_CLOSED => Os(libc::EBADF),
+            OUT_OF_DISK => Os(libc::ENOSPC),
+            UNEXPECTED_EOF => UnexpectedEof,
+            MMAP_FAILED => MmapFailed,
+
+            WRONG_LOG_MAGIC_NUMBER => WrongLogMagicNumber,
+            WRONG_LOG_MAJOR_VERSION => WrongLogMajorVersion,
+            UNSUPPORTED_LOG_MINOR_VERSION => UnsupportedLogMinorVersion,
+            LOG_TOO_SMALL => LogTooSmall,
+            LOG_CLOSED => LogClosed,
+            LOG_ITERATOR_INACTIVE => LogIteratorInactive,
+            LOG_ITERATOR_MISMATCH => LogIteratorMismatch,
+            LOG_ITERATOR_CLOSED => LogIteratorClosed,
+            LOG_HEADER_CORRUPT => LogHeaderCorrupt,
+            INVALID_COMPRESSION_BLOCK_SIZE => InvalidCompressionBlockSize,
+            INVALID_COMPRESSION_TYPE => InvalidCompressionType,
+
+            WRONG_HASH_MAGIC_NUMBER => WrongHashMagicNumber,
+            WRONG_HASH_MAJOR_VERSION => WrongHashMajorVersion,
+            UNSUPPORTED_HASH_MINOR_VERSION => UnsupportedHashMinorVersion,
+            HASH_TOO_SMALL => HashTooSmall,
+            HASH_CLOSED => HashClosed,
+            FILE_IDENTIFIER_MISMATCH => FileIdentifierMismatch,
+            HASH_HEADER_CORRUPT => HashHeaderCorrupt,
+            HASH_SIZE_INVALID => HashSizeInvalid,
+        }
+    }
+
+    /// The same category as `Error::kind` gives for the corresponding `Error`.
+    #[cfg(feature = "core-error")]
+    pub fn kind(self) -> ErrorKind {
+        use self::Code::*;
+
+        match self {
+            Os(libc::ENOENT) => ErrorKind::NotFound,
+            Os(libc::EOVERFLOW) | Os(libc::EFBIG) => ErrorKind::TooLarge,
+            Os(_) | MmapFailed => ErrorKind::Io,
+
+            WrongLogMagicNumber
+            | WrongLogMajorVersion
+            | UnsupportedLogMinorVersion
+            | WrongHashMagicNumber
+            | WrongHashMajorVersion
+            | UnsupportedHashMinorVersion
+            | FileIdentifierMismatch => ErrorKind::WrongType,
+
+            UnexpectedEof | LogTooSmall | LogHeaderCorrupt | HashTooSmall | HashHeaderCorrupt => {
+                ErrorKind::Corrupt
+            }
+
+            InvalidCompressionBlockSize | InvalidCompressionType | HashSizeInvalid => {
+                ErrorKind::InvalidInput
+            }
+
+            LogClosed | LogIteratorInactive | LogIteratorMismatch | LogIteratorClosed
+            | HashClosed => ErrorKind::InvalidState,
+
+            Internal => ErrorKind::Internal,
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Code::*;
+
+        match self {
+            Os(raw) => write!(f, "OS error {}", raw),
+            Internal => f.write_str("internal error"),
+            UnexpectedEof => f.write_str("unexpected end-of-file"),
+            MmapFailed => f.write_str("failed to mmap()"),
+
+            WrongLogMagicNumber => f.write_str("wrong log magic number"),
+            WrongLogMajorVersion => f.write_str("wrong log major version"),
+            UnsupportedLogMinorVersion => f.write_str("unsupported log minor version"),
+            LogTooSmall => f.write_str("log too small"),
+            LogClosed => f.write_str("log closed"),
+            LogIteratorInactive => f.write_str("log iterator inactive"),
+            LogIteratorMismatch => f.write_str("log iterator mismatch"),
+            LogIteratorClosed => f.write_str("log iterator closed"),
+            LogHeaderCorrupt => f.write_str("log header corrupt"),
+            InvalidCompressionBlockSize => f.write_str("invalid compression block size"),
+            InvalidCompressionType => f.write_str("invalid compression type"),
+
+            WrongHashMagicNumber => f.write_str("wrong hash magic number"),
+            WrongHashMajorVersion => f.write_str("wrong hash major version"),
+            UnsupportedHashMinorVersion => f.write_str("unsupported hash minor version"),
+            HashTooSmall => f.write_str("hash too small"),
+            HashClosed => f.write_str("hash closed"),
+            FileIdentifierMismatch => f.write_str("file identifier mismatch"),
+            HashHeaderCorrupt => f.write_str("hash header corrupt"),
+            HashSizeInvalid => f.write_str("hash size invalid"),
+        }
+    }
+}
+
+#[cfg(feature = "core-error")]
+impl core::error::Error for Code {}
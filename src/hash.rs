@@ -1,6 +1,16 @@
+use std::collections;
+use std::convert;
+use std::fs;
+use std::io;
+use std::iter;
+use std::mem;
+use std::ops;
 use std::os;
 use std::path;
 use std::ptr;
+use std::slice;
+use std::sync;
+use std::vec;
 
 use sparkey_sys::*;
 
@@ -8,21 +18,173 @@ use crate::error;
 use crate::log;
 use crate::util;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(non_camel_case_types)]
 pub enum Type {
     Murmur3_32,
     Murmur3_64,
 }
 
+// Size of the (version 1.1) hash header; the hash table starts right after it.
+const HASH_HEADER_SIZE: u64 = 112;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HashSize {
+    Auto,
+    Bits32,
+    Bits64,
+}
+
 pub struct Writer;
 
+/// Writes a log and builds its hash index, then opens both as a `Reader`.
+///
+/// The C library can only build an index over a finished log, so entries are written to the log
+/// as they are added and the index is built in one pass by `finish`.  `map::Map::create` returns
+/// one of these for the paired `<base>.spl` and `<base>.spi`.
+pub struct Builder {
+    log_writer: log::Writer,
+    log_path: path::PathBuf,
+    hash_path: path::PathBuf,
+    hash_size: HashSize,
+}
+
+/// The metadata stored in a hash index's header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HashHeader {
+    pub major_version: u32,
+    pub minor_version: u32,
+    /// Random identifier shared by the log and the hash index built from it.
+    pub file_identifier: u32,
+    pub hash_seed: u32,
+    /// Offset of the end of the log data covered by the index.
+    pub data_end: u64,
+    pub max_key_len: u64,
+    pub max_value_len: u64,
+    pub num_puts: u64,
+    /// Size of the log entries that are overwritten or deleted, in bytes.
+    pub garbage_size: u64,
+    /// Number of live keys.
+    pub num_entries: u64,
+    /// Size of log addresses in the index, in bytes.
+    pub address_size: u32,
+    pub hash_size: HashSize,
+    pub hash_capacity: u64,
+    pub max_displacement: u64,
+    pub hash_collisions: u64,
+    pub total_displacement: u64,
+}
+
+/// Whether a key is live, was deleted, or was never written, as found by `Reader::key_status`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyStatus {
+    Present,
+    Deleted,
+    Absent,
+}
+
 pub struct Reader(*mut hashreader, log::Reader);
 
-impl Type {
-    fn as_raw(&self) -> os::raw::c_int {
-        match *self {
-            Type::Murmur3_32 => 4,
-            Type::Murmur3_64 => 8,
+/// A cheaply cloneable handle to a `Reader`, for sharing between threads.
+///
+/// Lookups and iterators each create their own log iterator and the index is only read, so
+/// clones can be used concurrently.
+#[derive(Clone)]
+pub struct SharedReader(sync::Arc<Reader>);
+
+pub struct Iter<'a>(log::Entries<'a>);
+
+/// A value read directly from the memory-mapped log, without copying.
+#[derive(Debug)]
+pub struct MmapValue<'a>(&'a [u8]);
+
+pub struct PrefixScan<'a>(Iter<'a>, Vec<u8>);
+
+/// A position among the live entries of a log, moved by key lookups and by advancing.
+///
+/// The C API only iterates forward, so there is no way to step back to the previous entry; seek
+/// to its key instead.
+pub struct Cursor<'a> {
+    entries: log::Entries<'a>,
+    current: Option<log::Entry>,
+}
+
+pub fn write<P1, P2>(hash_path: P1, log_path: P2, hash_size: HashSize) -> error::Result<()>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+{
+    let hash_path = util::path_to_cstring(hash_path)?;
+    let log_path = util::path_to_cstring(log_path)?;
+
+    util::handle(unsafe { hash_write(hash_path.as_ptr(), log_path.as_ptr(), hash_size.as_raw()) })
+}
+
+/// Like `write`, but writes the index to `<hash_path>.tmp` first and renames it over `hash_path`,
+/// so readers opening `hash_path` see either the old or the new index, never a partial one.
+///
+/// The rename is only atomic if both paths are on the same filesystem, which they are unless
+/// `hash_path` itself is a mount point.  Readers that already have the old index open keep using
+/// it.
+pub fn write_atomic<P1, P2>(hash_path: P1, log_path: P2, hash_size: HashSize) -> error::Result<()>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+{
+    let hash_path = hash_path.as_ref();
+    let mut tmp_path = hash_path.as_os_str().to_os_string();
+    tmp_path.push(".tmp");
+
+    write(&tmp_path, log_path, hash_size)?;
+    fs::rename(&tmp_path, hash_path).map_err(error::Error::IO)
+}
+
+/// Computes the size in bytes of the hash index `write` would build from scratch for the log at
+/// `log_path`, without building it.
+///
+/// Rewriting an existing index for the same log sizes the table by its live entries instead, so
+/// the result is then an upper bound.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn estimate_size<P>(log_path: P, hash_size: HashSize) -> error::Result<u64>
+where
+    P: AsRef<path::Path>,
+{
+    let header = log::Reader::open(log_path)?.header();
+
+    // The same sizing as the C writer: 30% headroom, an odd capacity, and addresses that fit the
+    // log's data and entry indices.
+    let capacity = 1 | (header.num_puts as f64 * 1.3) as u64;
+    let entry_block_bits = 32 - header.max_entries_per_block.leading_zeros();
+    let address_size = if header.data_end < 1 << (32 - entry_block_bits) {
+        4
+    } else {
+        8
+    };
+    let hash_size = match hash_size {
+        HashSize::Auto if capacity >= 1 << 23 => 8,
+        HashSize::Auto | HashSize::Bits32 => 4,
+        HashSize::Bits64 => 8,
+    };
+
+    Ok(HASH_HEADER_SIZE + capacity * (hash_size + address_size))
+}
+
+impl HashSize {
+    pub fn as_raw(self) -> os::raw::c_int {
+        match self {
+            HashSize::Auto => 0,
+            HashSize::Bits32 => 4,
+            HashSize::Bits64 => 8,
+        }
+    }
+}
+
+impl From<Type> for HashSize {
+    fn from(value: Type) -> Self {
+        match value {
+            Type::Murmur3_32 => HashSize::Bits32,
+            Type::Murmur3_64 => HashSize::Bits64,
         }
     }
 }
@@ -33,18 +195,124 @@ impl Writer {
         P1: AsRef<path::Path>,
         P2: AsRef<path::Path>,
     {
-        let hash_path = util::path_to_cstring(hash_path)?;
-        let log_path = util::path_to_cstring(log_path)?;
-
-        util::handle(unsafe {
-            hash_write(
-                hash_path.as_ptr(),
-                log_path.as_ptr(),
-                hash_type.map_or(0, |t| t.as_raw()),
-            )
-        })?;
+        write(
+            hash_path,
+            log_path,
+            hash_type.map_or(HashSize::Auto, From::from),
+        )
+    }
+}
 
-        Ok(())
+impl Builder {
+    /// Creates the log at `log_path`, truncating any existing one; the index is written to
+    /// `hash_path` by `finish`.
+    pub fn create<P1, P2>(
+        hash_path: P1,
+        log_path: P2,
+        config: log::WriterConfig,
+    ) -> error::Result<Self>
+    where
+        P1: AsRef<path::Path>,
+        P2: AsRef<path::Path>,
+    {
+        let log_path = log_path.as_ref().to_path_buf();
+        let log_writer = log::Writer::create_with(&log_path, config)?;
+
+        Ok(Builder {
+            log_writer,
+            log_path,
+            hash_path: hash_path.as_ref().to_path_buf(),
+            hash_size: HashSize::Auto,
+        })
+    }
+
+    /// Opens the existing log at `log_path` for adding entries to its end; the index at
+    /// `hash_path` is only replaced by `finalize` or `finish`.
+    pub fn append<P1, P2>(hash_path: P1, log_path: P2) -> error::Result<Self>
+    where
+        P1: AsRef<path::Path>,
+        P2: AsRef<path::Path>,
+    {
+        let log_path = log_path.as_ref().to_path_buf();
+        let log_writer = log::Writer::append(&log_path)?;
+
+        Ok(Builder {
+            log_writer,
+            log_path,
+            hash_path: hash_path.as_ref().to_path_buf(),
+            hash_size: HashSize::Auto,
+        })
+    }
+
+    /// Sets the size of the hashes in the index.  Defaults to `HashSize::Auto`.
+    pub fn set_hash_size(&mut self, hash_size: HashSize) {
+        self.hash_size = hash_size;
+    }
+
+    pub fn log_writer(&mut self) -> &mut log::Writer {
+        &mut self.log_writer
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> error::Result<()> {
+        self.log_writer.put(key, value)
+    }
+
+    pub fn delete(&mut self, key: &[u8]) -> error::Result<()> {
+        self.log_writer.delete(key)
+    }
+
+    /// Closes the log and writes its index without opening them.
+    ///
+    /// The index is written with `write_atomic`, so readers never see a partially written index.
+    pub fn finalize(self) -> error::Result<()> {
+        let Builder {
+            log_writer,
+            log_path,
+            hash_path,
+            hash_size,
+        } = self;
+
+        log_writer.close()?;
+        write_atomic(&hash_path, &log_path, hash_size)
+    }
+
+    /// Closes the log, writes its index and opens both.
+    pub fn finish(self) -> error::Result<Reader> {
+        let log_path = self.log_path.clone();
+        let hash_path = self.hash_path.clone();
+
+        self.finalize()?;
+
+        Reader::open(hash_path, log_path)
+    }
+}
+
+impl HashHeader {
+    fn from_raw(raw: &hashheader) -> error::Result<Self> {
+        let hash_size = match raw.hash_size {
+            4 => HashSize::Bits32,
+            8 => HashSize::Bits64,
+            _ => return Err(error::Error::HashHeaderCorrupt),
+        };
+
+        Ok(HashHeader {
+            major_version: raw.major_version,
+            minor_version: raw.minor_version,
+            file_identifier: raw.file_identifier,
+            hash_seed: raw.hash_seed,
+            data_end: raw.data_end,
+            max_key_len: raw.max_key_len,
+            max_value_len: raw.max_value_len,
+            num_puts: raw.num_puts,
+            garbage_size: raw.garbage_size,
+            num_entries: raw.num_entries,
+            address_size: raw.address_size,
+            hash_size,
+            hash_capacity: raw.hash_capacity,
+            max_displacement: raw.max_displacement,
+            hash_collisions: raw.hash_collisions,
+            total_displacement: raw.total_displacement,
+        })
     }
 }
 
@@ -55,12 +323,13 @@ impl Reader {
         P2: AsRef<path::Path>,
     {
         let mut raw = ptr::null_mut();
+        let log_path = log_path.as_ref().to_path_buf();
         let hash_path = util::path_to_cstring(hash_path)?;
-        let log_path = util::path_to_cstring(log_path)?;
+        let log_path_c = util::path_to_cstring(&log_path)?;
 
-        util::handle(unsafe { hash_open(&mut raw, hash_path.as_ptr(), log_path.as_ptr()) })?;
+        util::handle(unsafe { hash_open(&mut raw, hash_path.as_ptr(), log_path_c.as_ptr()) })?;
 
-        let log_reader = unsafe { log::Reader::from_raw(hash_getreader(raw)) };
+        let log_reader = unsafe { log::Reader::from_raw_with_path(hash_getreader(raw), log_path) };
 
         Ok(Self(raw, log_reader))
     }
@@ -70,27 +339,196 @@ impl Reader {
     }
 
     pub fn get(&self, key: &[u8]) -> error::Result<Option<bytes::BytesMut>> {
-        let log_reader = self.log_reader().as_raw();
-        let mut log_iter = ptr::null_mut();
+        self.with_iter(|iter| {
+            if self.seek(iter, key)? {
+                Ok(Some(util::read_value(iter, self.1.as_raw())?))
+            } else {
+                Ok(None)
+            }
+        })
+    }
 
-        util::handle(unsafe { logiter_create(&mut log_iter, log_reader) })?;
+    /// Looks up `key`, returning the value from `default` if it is not live.
+    pub fn get_or<F>(&self, key: &[u8], default: F) -> error::Result<bytes::BytesMut>
+    where
+        F: FnOnce() -> bytes::BytesMut,
+    {
+        Ok(self.get(key)?.unwrap_or_else(default))
+    }
 
-        util::handle(unsafe { hash_get(self.0, key.as_ptr(), key.len() as u64, log_iter) })?;
+    /// Looks up `key`, returning an empty value if it is not live.
+    pub fn get_or_default(&self, key: &[u8]) -> error::Result<bytes::BytesMut> {
+        self.get_or(key, bytes::BytesMut::new)
+    }
 
-        let result = match unsafe { logiter_state(log_iter) } {
-            iter_state::ITER_ACTIVE => {
-                let value = util::read_value(log_iter, log_reader)?;
-                Some(value)
+    /// Looks up `key` and reads its value into `buf`, replacing its contents, and returns the
+    /// length of the value.
+    ///
+    /// `buf` is grown to hold the longest value in the log on first use, so reusing it for further
+    /// lookups doesn't allocate.
+    pub fn get_into(&self, key: &[u8], buf: &mut Vec<u8>) -> error::Result<Option<usize>> {
+        use std::convert::TryFrom;
+
+        buf.clear();
+        buf.reserve(usize::try_from(self.max_value_len()).unwrap());
+
+        self.with_iter(|iter| {
+            if self.seek(iter, key)? {
+                util::fill_value(iter, self.1.as_raw(), buf)?;
+                Ok(Some(buf.len()))
+            } else {
+                Ok(None)
             }
-            _ => None,
-        };
+        })
+    }
+
+    /// Looks up `key` and borrows its value straight from the memory-mapped log.
+    ///
+    /// Only uncompressed logs store values contiguously in the file; for compressed logs this fails
+    /// with `LogCompressed`.
+    pub fn get_mmap(&self, key: &[u8]) -> error::Result<Option<MmapValue<'_>>> {
+        use std::convert::TryFrom;
+
+        if self.1.compression_type() != log::CompressionType::None {
+            return Err(error::Error::LogCompressed);
+        }
+
+        self.with_iter(|iter| {
+            if !self.seek(iter, key)? {
+                return Ok(None);
+            }
+
+            let expected_len = unsafe { logiter_valuelen(iter) };
+            if expected_len == 0 {
+                return Ok(Some(MmapValue(&[])));
+            }
+
+            let mut chunk = ptr::null_mut();
+            let mut len = 0;
+
+            util::handle(unsafe {
+                logiter_valuechunk(iter, self.1.as_raw(), expected_len, &mut chunk, &mut len)
+            })?;
+            assert_eq!(expected_len, len);
+
+            // Uncompressed blocks point into the log's mapping, which outlives the iterator.
+            Ok(Some(MmapValue(unsafe {
+                slice::from_raw_parts(chunk, usize::try_from(len).unwrap())
+            })))
+        })
+    }
+
+    /// Looks up several keys, returning their values in the same order.  All lookups share one
+    /// log iterator, so this creates one iterator instead of one per key as `get` would.
+    pub fn get_many(&self, keys: &[&[u8]]) -> error::Result<Vec<Option<bytes::BytesMut>>> {
+        self.with_iter(|iter| {
+            keys.iter()
+                .map(|key| {
+                    if self.seek(iter, key)? {
+                        Ok(Some(util::read_value(iter, self.1.as_raw())?))
+                    } else {
+                        Ok(None)
+                    }
+                })
+                .collect()
+        })
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> error::Result<bool> {
+        self.with_iter(|iter| self.seek(iter, key))
+    }
+
+    /// Finds whether `key` is live, deleted or absent.
+    ///
+    /// The index only records live keys, so it can't tell deleted keys from absent ones.  Keys
+    /// that are not live are looked for in the log instead, which means scanning all of it on
+    /// every miss, O(n) in the size of the log; only keys are read, not values.  They are
+    /// `Deleted` if their last entry is a delete and `Absent` if there is none.  A last entry
+    /// that is a put means the index doesn't cover the log, which fails with `StaleIndex`.
+    pub fn key_status(&self, key: &[u8]) -> error::Result<KeyStatus> {
+        if self.contains_key(key)? {
+            return Ok(KeyStatus::Present);
+        }
+
+        let log = self.1.as_raw();
+        let last = self.with_iter(|iter| {
+            let mut last = None;
+            let mut buf = Vec::new();
+
+            loop {
+                util::handle(unsafe { logiter_next(iter, log) })?;
+                match unsafe { logiter_state(iter) } {
+                    iter_state::ITER_ACTIVE => {}
+                    _ => return Ok(last),
+                }
+                if unsafe { logiter_keylen(iter) } != key.len() as u64 {
+                    continue;
+                }
+                util::fill_key(iter, log, &mut buf)?;
+                if buf == key {
+                    last = Some(unsafe { logiter_type(iter) });
+                }
+            }
+        })?;
+
+        match last {
+            None => Ok(KeyStatus::Absent),
+            Some(entry_type::ENTRY_DELETE) => Ok(KeyStatus::Deleted),
+            Some(entry_type::ENTRY_PUT) => {
+                let index_data_end = self.header()?.data_end;
+                let log_data_end = self.1.header().data_end;
+                if index_data_end == log_data_end {
+                    return Err(error::Error::HashHeaderCorrupt);
+                }
+                Err(error::Error::StaleIndex {
+                    index_data_end,
+                    log_data_end,
+                })
+            }
+        }
+    }
 
-        unsafe { logiter_close(&mut log_iter) };
+    /// Iterates over the live key/value pairs in log order, yielding each key once with its
+    /// current value.
+    pub fn iter(&self) -> error::Result<Iter<'_>> {
+        Ok(Iter(self.entries()?))
+    }
 
-        Ok(result)
+    /// Iterates over the live key/value pairs whose key starts with `prefix`.  The index is not
+    /// ordered, so this scans the whole log regardless of how many keys match.
+    pub fn prefix_scan(&self, prefix: &[u8]) -> error::Result<PrefixScan<'_>> {
+        Ok(PrefixScan(self.iter()?, prefix.to_vec()))
     }
 
-    pub fn entries(&self) -> error::Result<log::Entries> {
+    /// Reads all live key/value pairs into a `HashMap`.
+    ///
+    /// Uses the hash index, so each key appears once with its current value and deleted keys are
+    /// left out.  Everything is held in memory, so this is only meant for small databases.
+    pub fn to_hashmap(
+        &self,
+    ) -> error::Result<collections::HashMap<bytes::BytesMut, bytes::BytesMut>> {
+        self.iter()?.collect()
+    }
+
+    /// Creates a cursor positioned before the first live entry.
+    pub fn cursor(&self) -> error::Result<Cursor<'_>> {
+        Ok(Cursor {
+            entries: self.entries()?,
+            current: None,
+        })
+    }
+
+    /// Reads all live key/value pairs into a `BTreeMap`, ordered by key.
+    ///
+    /// Like `to_hashmap`, but the sorted order makes the output of two databases easy to compare
+    /// key by key.  Everything is held in memory, so this is only meant for small databases.
+    pub fn to_btreemap(
+        &self,
+    ) -> error::Result<collections::BTreeMap<bytes::BytesMut, bytes::BytesMut>> {
+        self.iter()?.collect()
+    }
+
+    pub fn entries(&self) -> error::Result<log::Entries<'_>> {
         let mut raw = ptr::null_mut();
 
         util::handle(unsafe { logiter_create(&mut raw, self.1.as_raw()) })?;
@@ -98,7 +536,11 @@ impl Reader {
         Ok(unsafe { log::Entries::from_raw(raw, &self.1, Some(self.0)) })
     }
 
-    pub fn keys(&self) -> error::Result<log::Keys> {
+    /// Iterates over the live keys in log order, like `iter` without the values.
+    ///
+    /// Each key appears once, at the position of its current put; deletes and overwritten puts
+    /// are skipped.  `log::Reader::keys` yields every key in the log instead.
+    pub fn keys(&self) -> error::Result<log::Keys<'_>> {
         let mut raw = ptr::null_mut();
 
         util::handle(unsafe { logiter_create(&mut raw, self.1.as_raw()) })?;
@@ -106,7 +548,19 @@ impl Reader {
         Ok(unsafe { log::Keys::from_raw(raw, &self.1, Some(self.0)) })
     }
 
-    pub fn values(&self) -> error::Result<log::Values> {
+    /// The live keys in reverse log order.
+    ///
+    /// Sparkey logs can only be iterated forwards (entries have variable lengths and blocks may be
+    /// compressed), so this reads all live keys into memory first and then yields them backwards.
+    pub fn keys_rev(&self) -> error::Result<iter::Rev<vec::IntoIter<bytes::BytesMut>>> {
+        let keys = self.keys()?.collect::<error::Result<Vec<_>>>()?;
+
+        Ok(keys.into_iter().rev())
+    }
+
+    /// Iterates over the current values of the live keys in log order, like `iter` without the
+    /// keys.
+    pub fn values(&self) -> error::Result<log::Values<'_>> {
         let mut raw = ptr::null_mut();
 
         util::handle(unsafe { logiter_create(&mut raw, self.1.as_raw()) })?;
@@ -114,13 +568,154 @@ impl Reader {
         Ok(unsafe { log::Values::from_raw(raw, &self.1, Some(self.0)) })
     }
 
+    pub fn max_key_len(&self) -> u64 {
+        self.1.max_key_len()
+    }
+
+    pub fn max_value_len(&self) -> u64 {
+        self.1.max_value_len()
+    }
+
+    pub fn compression_type(&self) -> log::CompressionType {
+        self.1.compression_type()
+    }
+
+    pub fn compression_block_size(&self) -> u32 {
+        self.1.compression_block_size()
+    }
+
+    /// Checks that the index covers the whole log, failing with `StaleIndex` if entries were
+    /// added to the log after the index was written, or `FileIdentifierMismatch` if the log was
+    /// replaced.  Lookups and iteration through a stale index miss the newer entries.
+    ///
+    /// The index is the one this reader has open, while the log header is read from disk again,
+    /// so this also catches appends and indexes written since the reader was opened.
+    pub fn validate_against_log(&self) -> error::Result<()> {
+        let hash_header = self.header()?;
+        let log_header = match self.1.path() {
+            Some(_) => self.1.header_on_disk()?,
+            None => self.1.header(),
+        };
+
+        if hash_header.file_identifier != log_header.file_identifier {
+            return Err(error::Error::FileIdentifierMismatch);
+        }
+        if hash_header.data_end != log_header.data_end {
+            return Err(error::Error::StaleIndex {
+                index_data_end: hash_header.data_end,
+                log_data_end: log_header.data_end,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The header of the index this reader has open.  It doesn't change when the file is
+    /// replaced on disk, for example by `write_atomic`; open a new reader to see the new index.
+    pub fn header(&self) -> error::Result<HashHeader> {
+        let mut raw = unsafe { mem::zeroed::<hashheader>() };
+
+        unsafe { hashreader_header(self.0, &mut raw) };
+
+        HashHeader::from_raw(&raw)
+    }
+
+    /// Whether the index uses 32- or 64-bit hashes; never `HashSize::Auto`.
+    pub fn hash_size(&self) -> error::Result<HashSize> {
+        Ok(self.header()?.hash_size)
+    }
+
     pub fn num_entries(&self) -> u64 {
         unsafe { hash_numentries(self.0) }
     }
 
+    /// The number of live keys in the index; deleted keys and overwritten values are not counted.
+    pub fn len(&self) -> u64 {
+        self.num_entries()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn num_collisions(&self) -> u64 {
         unsafe { hash_numcollisions(self.0) }
     }
+
+    /// Writes every live key/value pair as a `key,value` CSV record, quoted as described in
+    /// RFC 4180.  Fails with `Error::InvalidUtf8` at the first key or value that isn't UTF-8.
+    ///
+    /// Pairs are written one at a time, so `out` should usually be buffered.
+    pub fn export_csv<W>(&self, mut out: W) -> error::Result<()>
+    where
+        W: io::Write,
+    {
+        for pair in self.iter()? {
+            let (key, value) = pair?;
+            let fields = [util::to_str("key", &key)?, util::to_str("value", &value)?];
+
+            util::csv::write_record(&mut out, &fields).map_err(error::Error::IO)?;
+        }
+
+        out.flush().map_err(error::Error::IO)
+    }
+
+    /// Writes every live key/value pair as a line of JSON, in the format described on
+    /// `log::NdjsonEncoding`.
+    ///
+    /// Pairs are written one at a time, so `out` should usually be buffered.
+    #[cfg(feature = "ndjson")]
+    pub fn export_ndjson<W>(&self, mut out: W, encoding: log::NdjsonEncoding) -> error::Result<()>
+    where
+        W: io::Write,
+    {
+        for pair in self.iter()? {
+            let (key, value) = pair?;
+            let (key, key_utf8) = util::ndjson::encode(&key, encoding);
+            let (value, value_utf8) = util::ndjson::encode(&value, encoding);
+            let record = util::ndjson::Record {
+                entry_type: Some(log::EntryType::Put.to_string()),
+                key,
+                key_utf8,
+                value,
+                value_utf8,
+                delete: None,
+            };
+
+            serde_json::to_writer(&mut out, &record)
+                .map_err(|e| error::Error::IO(io::Error::from(e)))?;
+            out.write_all(b"\n").map_err(error::Error::IO)?;
+        }
+
+        out.flush().map_err(error::Error::IO)
+    }
+
+    fn with_iter<A, F>(&self, f: F) -> error::Result<A>
+    where
+        F: FnOnce(*mut logiter) -> error::Result<A>,
+    {
+        let mut iter = ptr::null_mut();
+
+        util::handle(unsafe { logiter_create(&mut iter, self.1.as_raw()) })?;
+
+        let result = f(iter);
+
+        unsafe { logiter_close(&mut iter) };
+
+        result
+    }
+
+    fn seek(&self, iter: *mut logiter, key: &[u8]) -> error::Result<bool> {
+        util::handle(unsafe { hash_get(self.0, key.as_ptr(), key.len() as u64, iter) })?;
+
+        match unsafe { logiter_state(iter) } {
+            iter_state::ITER_ACTIVE => match unsafe { logiter_type(iter) } {
+                entry_type::ENTRY_PUT => Ok(true),
+                entry_type::ENTRY_DELETE => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
 }
 
 impl Drop for Reader {
@@ -129,6 +724,158 @@ impl Drop for Reader {
     }
 }
 
+/// Serializes the live key/value pairs as a map; see `util::serde_bytes` for the byte encoding.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Reader {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::Error;
+        use serde::ser::SerializeMap;
+
+        let iter = self.iter().map_err(S::Error::custom)?;
+        let mut map = serializer.serialize_map(Some(iter.len()))?;
+        for pair in iter {
+            let (key, value) = pair.map_err(S::Error::custom)?;
+            map.serialize_entry(
+                &util::serde_bytes::Ref(&key),
+                &util::serde_bytes::Ref(&value),
+            )?;
+        }
+        map.end()
+    }
+}
+
+impl<'a> convert::TryFrom<&'a Reader> for collections::HashMap<bytes::BytesMut, bytes::BytesMut> {
+    type Error = error::Error;
+
+    fn try_from(reader: &'a Reader) -> error::Result<Self> {
+        reader.to_hashmap()
+    }
+}
+
+impl<'a> convert::TryFrom<&'a Reader> for collections::BTreeMap<bytes::BytesMut, bytes::BytesMut> {
+    type Error = error::Error;
+
+    fn try_from(reader: &'a Reader) -> error::Result<Self> {
+        reader.to_btreemap()
+    }
+}
+
 unsafe impl Send for Reader {}
 
 unsafe impl Sync for Reader {}
+
+impl SharedReader {
+    pub fn new(reader: Reader) -> Self {
+        SharedReader(sync::Arc::new(reader))
+    }
+
+    pub fn reader(&self) -> &Reader {
+        &self.0
+    }
+
+    pub fn get(&self, key: &[u8]) -> error::Result<Option<bytes::BytesMut>> {
+        self.0.get(key)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> error::Result<bool> {
+        self.0.contains_key(key)
+    }
+
+    pub fn iter(&self) -> error::Result<Iter<'_>> {
+        self.0.iter()
+    }
+}
+
+impl From<Reader> for SharedReader {
+    fn from(reader: Reader) -> Self {
+        SharedReader::new(reader)
+    }
+}
+
+impl<'a> ops::Deref for MmapValue<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> AsRef<[u8]> for MmapValue<'a> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl<'a> Cursor<'a> {
+    /// Moves to the live entry for `key`.  Returns `false` and moves past the last entry if the
+    /// key is not live.
+    pub fn seek(&mut self, key: &[u8]) -> error::Result<bool> {
+        self.current = None;
+
+        if self.entries.seek(key)? {
+            self.advance()
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Moves to the next live entry in log order.  Returns `false` once there are no more.
+    pub fn advance(&mut self) -> error::Result<bool> {
+        self.current = self.entries.next().transpose()?;
+
+        Ok(self.current.is_some())
+    }
+
+    /// Moves back before the first live entry.
+    pub fn rewind(&mut self) -> error::Result<()> {
+        self.current = None;
+        self.entries.reset()
+    }
+
+    /// The entry the cursor is at, if any.
+    pub fn current(&self) -> Option<&log::Entry> {
+        self.current.as_ref()
+    }
+
+    /// The position of the entry the cursor is at, if any.
+    pub fn offset(&self) -> Option<log::Offset> {
+        self.current.as_ref().and(self.entries.offset())
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = error::Result<(bytes::BytesMut, bytes::BytesMut)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|e| e.map(|e| (e.key, e.value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for Iter<'a> {}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+impl<'a> Iterator for PrefixScan<'a> {
+    type Item = error::Result<(bytes::BytesMut, bytes::BytesMut)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prefix = &self.1;
+        self.0.find(|pair| {
+            pair.as_ref()
+                .map_or(true, |(key, _)| key.starts_with(prefix))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.0.size_hint().1)
+    }
+}
+
+impl<'a> iter::FusedIterator for PrefixScan<'a> {}
@@ -1,18 +1,58 @@
+#[cfg(feature = "tokio")]
+pub mod async_map;
+// failure's derive expands to impls inside a const, which newer compilers lint.
+#[allow(non_local_definitions)]
 pub mod error;
 pub mod hash;
 pub mod log;
+pub mod map;
 mod util;
 
 #[cfg(test)]
+// The original read_small and write_small tests predate these lints.
+#[allow(clippy::manual_split_once, clippy::needless_borrow)]
 mod test {
     use super::*;
 
     extern crate tempdir;
 
+    use std::alloc;
+    use std::cell;
+    use std::cmp;
+    use std::collections;
     use std::fs;
     use std::io;
+    use std::mem;
     use std::path;
     use std::str;
+    use std::sync;
+    use std::thread;
+
+    /// Counts the heap allocations made by the current thread.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: cell::Cell<usize> = const { cell::Cell::new(0) };
+    }
+
+    unsafe impl alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+            alloc::System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, size: usize) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            alloc::System.realloc(ptr, layout, size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
 
     #[test]
     fn roundtrip() {
@@ -64,6 +104,3788 @@ mod test {
         );
     }
 
+    #[test]
+    fn get_absent_or_deleted() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::None, 0).unwrap();
+            writer.put(b"present", b"value").unwrap();
+            writer.put(b"deleted", b"value").unwrap();
+            writer.delete(b"deleted").unwrap();
+        }
+        hash::Writer::write(&hash, &log, None).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+
+        assert_eq!(&b"value"[..], &reader.get(b"present").unwrap().unwrap()[..]);
+        assert_eq!(None, reader.get(b"deleted").unwrap());
+        assert_eq!(None, reader.get(b"absent").unwrap());
+    }
+
+    #[test]
+    fn contains_key() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+            writer.put(b"present", b"value").unwrap();
+            writer.put(b"deleted", b"value").unwrap();
+            writer.delete(b"deleted").unwrap();
+        }
+        hash::Writer::write(&hash, &log, None).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+
+        assert!(reader.contains_key(b"present").unwrap());
+        assert!(!reader.contains_key(b"deleted").unwrap());
+        assert!(!reader.contains_key(b"absent").unwrap());
+    }
+
+    #[test]
+    fn write_hash() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::None, 0).unwrap();
+            writer.put(b"key", b"value").unwrap();
+        }
+
+        for (i, &size) in [
+            hash::HashSize::Auto,
+            hash::HashSize::Bits32,
+            hash::HashSize::Bits64,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let hash = dir.path().join(format!("data{}.spi", i));
+            hash::write(&hash, &log, size).unwrap();
+
+            let reader = hash::Reader::open(&hash, &log).unwrap();
+            assert_eq!(&b"value"[..], &reader.get(b"key").unwrap().unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn len() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::None, 0).unwrap();
+            hash::write(&hash, &log, hash::HashSize::Auto).unwrap();
+            assert!(hash::Reader::open(&hash, &log).unwrap().is_empty());
+
+            writer.put(b"a", b"1").unwrap();
+            writer.put(b"b", b"2").unwrap();
+            writer.put(b"c", b"3").unwrap();
+            writer.delete(b"b").unwrap();
+        }
+        hash::write(&hash, &log, hash::HashSize::Auto).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+
+        assert_eq!(2, reader.len());
+        assert!(!reader.is_empty());
+    }
+
+    #[test]
+    fn hash_iter() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+            writer.put(b"a", b"1").unwrap();
+            writer.put(b"b", b"2").unwrap();
+            writer.put(b"a", b"3").unwrap();
+            writer.put(b"c", b"4").unwrap();
+            writer.delete(b"b").unwrap();
+            writer.put(b"a", b"5").unwrap();
+        }
+        hash::write(&hash, &log, hash::HashSize::Auto).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+        let pairs = reader
+            .iter()
+            .unwrap()
+            .map(|p| p.map(|(k, v)| (k.to_vec(), v.to_vec())))
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                (b"c".to_vec(), b"4".to_vec()),
+                (b"a".to_vec(), b"5".to_vec()),
+            ],
+            pairs
+        );
+    }
+
+    #[test]
+    fn seek() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+            writer.put(b"a", b"1").unwrap();
+            writer.put(b"b", b"2").unwrap();
+            writer.put(b"c", b"3").unwrap();
+        }
+        hash::write(&hash, &log, hash::HashSize::Auto).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+
+        let mut entries = reader.entries().unwrap();
+        assert!(entries.seek(b"b").unwrap());
+        let entry = entries.next().unwrap().unwrap();
+        assert_eq!((&b"b"[..], &b"2"[..]), (&entry.key[..], &entry.value[..]));
+        assert_eq!(&b"c"[..], &entries.next().unwrap().unwrap().key[..]);
+        assert!(entries.next().is_none());
+
+        let mut keys = reader.keys().unwrap();
+        assert!(keys.seek(b"a").unwrap());
+        assert_eq!(&b"a"[..], &keys.next().unwrap().unwrap()[..]);
+        assert!(!keys.seek(b"absent").unwrap());
+        assert!(keys.next().is_none());
+
+        let mut values = reader.log_reader().values().unwrap();
+        match values.seek(b"a") {
+            Err(error::Error::NoHashIndex) => (),
+            other => panic!("unexpected seek result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reset() {
+        let dir = path::Path::new("testdata");
+        let reader = hash::Reader::open(dir.join("small.spi"), dir.join("small.spl")).unwrap();
+        let log_reader = reader.log_reader();
+
+        let mut entries = log_reader.entries().unwrap();
+        let first = entries.next().unwrap().unwrap();
+        let count = 1 + entries.by_ref().count();
+
+        entries.reset().unwrap();
+        let again = entries.next().unwrap().unwrap();
+        assert_eq!(first.key, again.key);
+        assert_eq!(first.value, again.value);
+        assert_eq!(count, 1 + entries.by_ref().count());
+
+        let mut keys = reader.keys().unwrap();
+        let first = keys.next().unwrap().unwrap();
+        keys.nth(3);
+        keys.reset().unwrap();
+        assert_eq!(first, keys.next().unwrap().unwrap());
+    }
+
+    #[test]
+    fn value_chunks() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let value = (0..10 * 1024 * 1024)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        {
+            let mut writer =
+                log::Writer::create(&log, log::CompressionType::Snappy, 64 * 1024).unwrap();
+            writer.put(b"small", b"value").unwrap();
+            writer.put(b"large", &value).unwrap();
+        }
+
+        let reader = log::Reader::open(&log).unwrap();
+        let mut keys = reader.keys().unwrap();
+        keys.next().unwrap().unwrap();
+        assert_eq!(&b"large"[..], &keys.next().unwrap().unwrap()[..]);
+
+        let mut actual = Vec::new();
+        let mut count = 0;
+        let mut chunks = keys.value_chunks().unwrap();
+        while let Some(chunk) = chunks.next_chunk().unwrap() {
+            actual.extend_from_slice(chunk);
+            count += 1;
+        }
+
+        assert!(count > 1);
+        assert_eq!(value, actual);
+        assert!(keys.next().is_none());
+    }
+
+    #[test]
+    fn value_reader() {
+        use std::io::Read;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+        let value = (0..100_000).map(|i| (i % 241) as u8).collect::<Vec<_>>();
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+            writer.put(b"key", &value).unwrap();
+            writer.put(b"other", b"value").unwrap();
+        }
+        hash::write(&hash, &log, hash::HashSize::Auto).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+        let mut keys = reader.keys().unwrap();
+        keys.next().unwrap().unwrap();
+
+        let mut value_reader = keys.value_reader().unwrap();
+        let mut head = [0; 7];
+        value_reader.read_exact(&mut head).unwrap();
+        assert_eq!(&value[..7], &head[..]);
+        assert_eq!(value.len() as u64 - 7, value_reader.remaining());
+
+        let mut tail = Vec::new();
+        value_reader.read_to_end(&mut tail).unwrap();
+        assert_eq!(&value[7..], &tail[..]);
+        assert_eq!(0, value_reader.read(&mut head).unwrap());
+
+        assert_eq!(&b"other"[..], &keys.next().unwrap().unwrap()[..]);
+    }
+
+    #[test]
+    fn put_entry() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src = dir.path().join("src.spl");
+        let dst = dir.path().join("dst.spl");
+
+        {
+            let mut writer = log::Writer::create(&src, log::CompressionType::None, 0).unwrap();
+            writer.put(b"a", b"1").unwrap();
+            writer.delete(b"a").unwrap();
+            writer.put(b"b", b"2").unwrap();
+            writer.delete(b"c").unwrap();
+        }
+
+        let src_reader = log::Reader::open(&src).unwrap();
+
+        {
+            let mut writer = log::Writer::create(&dst, log::CompressionType::Snappy, 1024).unwrap();
+            for entry in src_reader.entries().unwrap() {
+                writer.put_entry(&entry.unwrap()).unwrap();
+            }
+        }
+
+        let dst_reader = log::Reader::open(&dst).unwrap();
+        let summarize = |reader: &log::Reader| {
+            reader
+                .entries()
+                .unwrap()
+                .map(|e| {
+                    let e = e.unwrap();
+                    (e.entry_type, e.key.to_vec(), e.value.to_vec())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(4, summarize(&src_reader).len());
+        assert_eq!(summarize(&src_reader), summarize(&dst_reader));
+    }
+
+    #[test]
+    fn extend_pairs() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+        let hash = dir.path().join("data.spi");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+            writer
+                .extend_pairs((0..1000).map(|i| (format!("key{}", i), format!("value{}", i))))
+                .unwrap();
+        }
+        hash::write(&hash, &log, hash::HashSize::Auto).unwrap();
+
+        let reader = hash::Reader::open(&hash, &log).unwrap();
+
+        assert_eq!(1000, reader.len());
+        for i in 0..1000 {
+            let value = reader.get(format!("key{}", i).as_bytes()).unwrap().unwrap();
+            assert_eq!(format!("value{}", i).as_bytes(), &value[..]);
+        }
+    }
+
+    #[test]
+    fn map() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("data");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.put(b"a", b"3").unwrap();
+        writer.delete(b"b").unwrap();
+        let created = writer.close().unwrap();
+
+        assert!(dir.path().join("data.spl").exists());
+        assert!(dir.path().join("data.spi").exists());
+        assert_eq!(1, created.len());
+
+        let map = map::Map::open(&base).unwrap();
+
+        assert_eq!(1, map.len());
+        assert!(!map.is_empty());
+        assert_eq!(&b"3"[..], &map.get(b"a").unwrap().unwrap()[..]);
+        assert!(map.contains_key(b"a").unwrap());
+        assert!(!map.contains_key(b"b").unwrap());
+        assert_eq!(1, map.iter().unwrap().count());
+    }
+
+    #[test]
+    fn collect_log() {
+        let builder: log::Builder = (0..10u8).map(|i| (vec![i], vec![i; 3])).collect();
+        let path = builder.path().to_path_buf();
+
+        {
+            let reader = builder.reader().unwrap();
+            let entries = reader
+                .entries()
+                .unwrap()
+                .map(|e| e.unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(10, entries.len());
+            assert_eq!(&[9, 9, 9][..], &entries[9].value[..]);
+        }
+
+        assert!(path.exists());
+        drop(builder);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn reopen() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::None, 0).unwrap();
+            writer.put(b"a", b"1").unwrap();
+        }
+
+        let mut reader = log::Reader::open(&log).unwrap();
+        assert_eq!(1, reader.entries().unwrap().count());
+
+        {
+            let mut writer = log::Writer::append(&log).unwrap();
+            writer.put(b"b", b"2").unwrap();
+            writer.flush().unwrap();
+        }
+        assert_eq!(1, reader.entries().unwrap().count());
+
+        reader.reopen().unwrap();
+        let keys = reader
+            .keys()
+            .unwrap()
+            .map(|k| k.unwrap().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![b"a".to_vec(), b"b".to_vec()], keys);
+    }
+
+    #[test]
+    fn close_writer() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+
+        let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&log).unwrap();
+        let entry = reader.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(&b"value"[..], &entry.value[..]);
+    }
+
+    #[test]
+    fn exact_size() {
+        let dir = path::Path::new("testdata");
+        let reader = hash::Reader::open(dir.join("small.spi"), dir.join("small.spl")).unwrap();
+        let len = reader.len() as usize;
+
+        let mut iter = reader.iter().unwrap();
+        for consumed in 0..len {
+            assert_eq!(len - consumed, iter.len());
+            iter.next().unwrap().unwrap();
+        }
+        assert_eq!(0, iter.len());
+        assert!(iter.next().is_none());
+
+        let mut keys = reader.keys().unwrap();
+        keys.next().unwrap().unwrap();
+        assert_eq!((len - 1, Some(len - 1)), keys.size_hint());
+
+        assert_eq!(
+            (0, None),
+            reader.log_reader().entries().unwrap().size_hint()
+        );
+    }
+
+    #[test]
+    fn compression_block_size() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+
+        match log::Writer::create(&log, log::CompressionType::Snappy, 0) {
+            Err(error::Error::CompressionBlockSizeTooSmall { block_size: 0, .. }) => (),
+            other => panic!("unexpected create result: {:?}", other),
+        }
+        assert!(!log.exists());
+
+        let snappy = log::CompressionType::Snappy;
+        log::Writer::create(&log, snappy, snappy.block_size_hint()).unwrap();
+        assert_eq!(
+            4096,
+            log::Reader::open(&log).unwrap().compression_block_size()
+        );
+
+        log::Writer::create(&log, log::CompressionType::None, 4096).unwrap();
+        let reader = log::Reader::open(&log).unwrap();
+        assert_eq!(0, reader.compression_block_size());
+        assert_eq!(0, log::CompressionType::None.block_size_hint());
+    }
+
+    #[test]
+    fn entry_lengths() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log = dir.path().join("data.spl");
+
+        {
+            let mut writer = log::Writer::create(&log, log::CompressionType::Snappy, 1024).unwrap();
+            writer.put(b"a", &[0; 10]).unwrap();
+            writer.put(b"bb", &[0; 200]).unwrap();
+            writer.delete(b"a").unwrap();
+            writer.put(b"ccc", &[0; 3000]).unwrap();
+        }
+
+        let reader = log::Reader::open(&log).unwrap();
+        let mut entries = reader.entries().unwrap();
+        assert_eq!(None, entries.entry_value_len());
+
+        let mut key_total = 0;
+        let mut value_total = 0;
+        loop {
+            log::Entries::skip(&mut entries, 1).unwrap();
+            match (entries.entry_key_len(), entries.entry_value_len()) {
+                (Some(key_len), Some(value_len)) => {
+                    key_total += key_len;
+                    value_total += value_len;
+                }
+                _ => break,
+            }
+        }
+
+        assert_eq!(7, key_total);
+        assert_eq!(3210, value_total);
+    }
+
+    #[test]
+    fn compact() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+
+        let mut writer = map::Map::create(&src, log::CompressionType::None, 0).unwrap();
+        for i in 0..100u32 {
+            writer.put(&i.to_be_bytes(), &[0; 100]).unwrap();
+        }
+        for i in 0..50u32 {
+            writer.put(&i.to_be_bytes(), &[1; 100]).unwrap();
+        }
+        for i in 50..90u32 {
+            writer.delete(&i.to_be_bytes()).unwrap();
+        }
+        let src_map = writer.close().unwrap();
+
+        map::compact(&src, &dst, log::CompressionType::None, 0).unwrap();
+        let dst_map = map::Map::open(&dst).unwrap();
+
+        let live = |map: &map::Map| {
+            map.iter()
+                .unwrap()
+                .map(|p| {
+                    let (k, v) = p.unwrap();
+                    (k.to_vec(), v.to_vec())
+                })
+                .collect::<collections::BTreeMap<_, _>>()
+        };
+        assert_eq!(60, dst_map.len());
+        assert_eq!(live(&src_map), live(&dst_map));
+        assert_eq!(
+            dst_map.len() as usize,
+            dst_map
+                .hash_reader()
+                .log_reader()
+                .entries()
+                .unwrap()
+                .count()
+        );
+
+        let size = |path: path::PathBuf| fs::metadata(path).unwrap().len();
+        assert!(size(dst.with_extension("spl")) < size(src.with_extension("spl")));
+    }
+
+    #[test]
+    fn merge() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        let output = dir.path().join("output");
+
+        let mut writer = map::Map::create(&first, log::CompressionType::None, 0).unwrap();
+        writer.put(b"overridden", b"old").unwrap();
+        writer.put(b"deleted", b"old").unwrap();
+        writer.put(b"only-first", b"first").unwrap();
+        writer.delete(b"resurrected").unwrap();
+        writer.close().unwrap();
+
+        let mut writer = map::Map::create(&second, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put(b"overridden", b"new").unwrap();
+        writer.delete(b"deleted").unwrap();
+        writer.put(b"resurrected", b"second").unwrap();
+        writer.delete(b"put-after-delete").unwrap();
+        writer.put(b"put-after-delete", b"second").unwrap();
+        writer.close().unwrap();
+
+        map::merge(&[&first, &second], &output, log::CompressionType::None, 0).unwrap();
+        let merged = map::Map::open(&output).unwrap();
+
+        assert_eq!(4, merged.len());
+        assert_eq!(
+            &b"new"[..],
+            &merged.get(b"overridden").unwrap().unwrap()[..]
+        );
+        assert_eq!(None, merged.get(b"deleted").unwrap());
+        assert_eq!(
+            &b"first"[..],
+            &merged.get(b"only-first").unwrap().unwrap()[..]
+        );
+        assert_eq!(
+            &b"second"[..],
+            &merged.get(b"resurrected").unwrap().unwrap()[..]
+        );
+        assert_eq!(
+            &b"second"[..],
+            &merged.get(b"put-after-delete").unwrap().unwrap()[..]
+        );
+
+        map::merge(&[&second, &first], &output, log::CompressionType::None, 0).unwrap();
+        let merged = map::Map::open(&output).unwrap();
+
+        assert_eq!(
+            &b"old"[..],
+            &merged.get(b"overridden").unwrap().unwrap()[..]
+        );
+        assert_eq!(&b"old"[..], &merged.get(b"deleted").unwrap().unwrap()[..]);
+        assert_eq!(None, merged.get(b"resurrected").unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+
+        let mut writer = map::Map::create(&source, log::CompressionType::None, 0).unwrap();
+        writer.put(b"text", b"value").unwrap();
+        writer.put(b"\xff\x00binary", b"\x01\x02").unwrap();
+        writer.put(b"deleted", b"value").unwrap();
+        writer.delete(b"deleted").unwrap();
+        let source = writer.close().unwrap();
+
+        let json = serde_json::to_string(source.hash_reader()).unwrap();
+        let parsed: collections::BTreeMap<String, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(2, parsed.len());
+        assert_eq!("dmFsdWU=", parsed["dGV4dA=="]);
+
+        let mut writer = map::Map::create(&target, log::CompressionType::None, 0).unwrap();
+        writer
+            .log_writer()
+            .deserialize_from(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        let target = writer.close().unwrap();
+
+        assert_eq!(2, target.len());
+        assert_eq!(&b"value"[..], &target.get(b"text").unwrap().unwrap()[..]);
+        assert_eq!(
+            &b"\x01\x02"[..],
+            &target.get(b"\xff\x00binary").unwrap().unwrap()[..]
+        );
+
+        let entry = log::Entry {
+            entry_type: log::EntryType::Delete,
+            key: b"key"[..].into(),
+            value: bytes::BytesMut::new(),
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(r#"{"entry_type":"Delete","key":"a2V5","value":""}"#, json);
+        let parsed: log::Entry = serde_json::from_str(&json).unwrap();
+        assert_eq!(log::EntryType::Delete, parsed.entry_type);
+        assert_eq!(&b"key"[..], &parsed.key[..]);
+    }
+
+    #[test]
+    fn into_iter() {
+        let builder: log::Builder = vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), b"2".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        let reader = builder.reader().unwrap();
+
+        let mut looped = Vec::new();
+        for entry in &reader {
+            let entry = entry.unwrap();
+            looped.push((entry.key, entry.value));
+        }
+
+        let collected = reader
+            .entries()
+            .unwrap()
+            .map(|e| e.map(|e| (e.key, e.value)))
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(2, looped.len());
+        assert_eq!(collected, looped);
+    }
+
+    #[test]
+    fn collect_keys_values() {
+        let builder: log::Builder = vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), b"2".to_vec()),
+            (b"a".to_vec(), b"3".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        let reader = builder.reader().unwrap();
+
+        let keys = reader.collect_keys().unwrap();
+        let values = reader.collect_values().unwrap();
+
+        assert_eq!(reader.entries().unwrap().count(), keys.len());
+        assert_eq!(reader.entries().unwrap().count(), values.len());
+        assert_eq!(
+            vec![&b"a"[..], &b"b"[..], &b"a"[..]],
+            keys.iter().map(|k| &k[..]).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![&b"1"[..], &b"2"[..], &b"3"[..]],
+            values.iter().map(|v| &v[..]).collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_map() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("async");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.close().unwrap();
+
+        let hash_reader =
+            hash::Reader::open(base.with_extension("spi"), base.with_extension("spl")).unwrap();
+        let async_map = async_map::AsyncMap::new(hash_reader);
+
+        let value = async_map.get(b"key".to_vec()).await.unwrap();
+        assert_eq!(&b"value"[..], &value.unwrap()[..]);
+        assert_eq!(
+            None,
+            async_map.clone().get(b"absent".to_vec()).await.unwrap()
+        );
+    }
+
+    #[test]
+    fn prefix_scan() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("prefix");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"user:1", b"alice").unwrap();
+        writer.put(b"user:1:posts", b"old").unwrap();
+        writer.put(b"user:12", b"bob").unwrap();
+        writer.put(b"user:2", b"carol").unwrap();
+        writer.put(b"usr", b"typo").unwrap();
+        writer.put(b"user:1:posts", b"new").unwrap();
+        writer.delete(b"user:2").unwrap();
+        let map = writer.close().unwrap();
+
+        let scan = |prefix: &[u8]| {
+            let mut pairs = map
+                .hash_reader()
+                .prefix_scan(prefix)
+                .unwrap()
+                .map(|p| p.map(|(k, v)| (k.to_vec(), v.to_vec())))
+                .collect::<error::Result<Vec<_>>>()
+                .unwrap();
+            pairs.sort();
+            pairs
+        };
+
+        assert_eq!(
+            vec![
+                (b"user:1".to_vec(), b"alice".to_vec()),
+                (b"user:12".to_vec(), b"bob".to_vec()),
+                (b"user:1:posts".to_vec(), b"new".to_vec()),
+            ],
+            scan(b"user:1")
+        );
+        assert_eq!(
+            vec![(b"user:1:posts".to_vec(), b"new".to_vec())],
+            scan(b"user:1:")
+        );
+        assert_eq!(4, scan(b"us").len());
+        assert_eq!(4, scan(b"").len());
+        assert!(scan(b"user:2").is_empty());
+    }
+
+    #[test]
+    fn get_many() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("many");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 64).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.put(b"c", b"3").unwrap();
+        writer.delete(b"b").unwrap();
+        let map = writer.close().unwrap();
+
+        let keys: &[&[u8]] = &[b"c", b"absent", b"b", b"a", b"c"];
+        let values = map.hash_reader().get_many(keys).unwrap();
+
+        assert_eq!(keys.len(), values.len());
+        for (key, value) in keys.iter().zip(values) {
+            assert_eq!(map.get(key).unwrap(), value);
+        }
+        assert_eq!(
+            vec![
+                Some(&b"3"[..]),
+                None,
+                None,
+                Some(&b"1"[..]),
+                Some(&b"3"[..])
+            ],
+            map.hash_reader()
+                .get_many(keys)
+                .unwrap()
+                .iter()
+                .map(|v| v.as_ref().map(|v| &v[..]))
+                .collect::<Vec<_>>()
+        );
+        assert!(map.hash_reader().get_many(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn log_header() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("header");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 256).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"long key", b"longer value").unwrap();
+        writer.put(b"a", b"2").unwrap();
+        writer.delete(b"long key").unwrap();
+        let map = writer.close().unwrap();
+
+        let header = map.hash_reader().log_reader().header();
+        assert_eq!(1, header.major_version);
+        assert_eq!(3, header.num_puts);
+        assert_eq!(1, header.num_deletes);
+        assert_eq!(4, header.num_entries());
+        assert_eq!(8, header.max_key_len);
+        assert_eq!(12, header.max_value_len);
+        assert_eq!(log::CompressionType::Snappy, header.compression_type);
+        assert_eq!(256, header.compression_block_size);
+        assert!(header.data_end > 84);
+
+        let reader = log::Reader::open(base.with_extension("spl")).unwrap();
+        assert_eq!(header, reader.header());
+        assert_eq!(header, reader.header_on_disk().unwrap());
+
+        let raw = unsafe { log::Reader::from_raw(reader.as_raw()) };
+        assert_eq!(header, raw.header());
+        match raw.header_on_disk() {
+            Err(error::Error::NoPath) => {}
+            other => panic!("expected NoPath, got {:?}", other),
+        }
+
+        // Only the header on disk sees entries appended after the reader was opened.
+        let mut writer = log::Writer::append(base.with_extension("spl")).unwrap();
+        writer.put(b"b", b"3").unwrap();
+        writer.close().unwrap();
+        assert_eq!(header, reader.header());
+        assert_eq!(4, reader.header_on_disk().unwrap().num_puts);
+    }
+
+    #[test]
+    fn keys_rev() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("rev");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        for i in 0..10 {
+            writer
+                .put(format!("key{}", i).as_bytes(), b"value")
+                .unwrap();
+        }
+        writer.delete(b"key3").unwrap();
+        let map = writer.close().unwrap();
+
+        let mut forward = map
+            .hash_reader()
+            .keys()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        let reversed = map.hash_reader().keys_rev().unwrap().collect::<Vec<_>>();
+
+        assert_eq!(9, reversed.len());
+        forward.reverse();
+        assert_eq!(forward, reversed);
+        assert_eq!(&b"key9"[..], &reversed[0][..]);
+    }
+
+    #[test]
+    fn for_each_borrowed() {
+        let builder = log::Builder::try_from_iter(
+            (0..1000).map(|i| (format!("key{:04}", i), format!("value{:04}", i))),
+        )
+        .unwrap();
+        let reader = builder.reader().unwrap();
+        let mut entries = reader.entries().unwrap();
+
+        let mut count = 0;
+        let mut total_len = 0;
+        let before = ALLOCATIONS.with(|n| n.get());
+        entries
+            .for_each_borrowed(|entry_type, key, value| {
+                assert_eq!(log::EntryType::Put, entry_type);
+                assert_eq!(&key[3..], &value[5..]);
+                count += 1;
+                total_len += key.len() + value.len();
+            })
+            .unwrap();
+        let allocations = ALLOCATIONS.with(|n| n.get()) - before;
+
+        assert_eq!(1000, count);
+        assert_eq!(1000 * (7 + 9), total_len);
+        assert!(allocations <= 2, "{} allocations", allocations);
+    }
+
+    #[test]
+    fn put_reader() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("put_reader.spl");
+        let value = (0..10_000u32).map(|i| i as u8).collect::<Vec<_>>();
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        writer
+            .put_reader(b"file", value.len() as u64, io::Cursor::new(&value))
+            .unwrap();
+        match writer.put_reader(b"short", 11, io::Cursor::new(&value[..10])) {
+            Err(error::Error::ValueTooShort {
+                expected: 11,
+                actual: 10,
+            }) => {}
+            other => panic!("expected ValueTooShort, got {:?}", other),
+        }
+        match writer.put_reader(b"long", 9, io::Cursor::new(&value[..10])) {
+            Err(error::Error::ValueTooLong { expected: 9 }) => {}
+            other => panic!("expected ValueTooLong, got {:?}", other),
+        }
+        // A wrong length fails on the short read instead of allocating it up front.
+        match writer.put_reader(b"huge", u64::MAX >> 1, io::Cursor::new(&value[..10])) {
+            Err(error::Error::ValueTooShort { actual: 10, .. }) => {}
+            Err(error::Error::TooLarge { .. }) if cfg!(target_pointer_width = "32") => {}
+            other => panic!("expected ValueTooShort, got {:?}", other),
+        }
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let entries = reader
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(&b"file"[..], &entries[0].key[..]);
+        assert_eq!(&value[..], &entries[0].value[..]);
+    }
+
+    #[test]
+    fn error_kinds() {
+        use error::ErrorKind::*;
+        use sparkey_sys::returncode::*;
+
+        let codes = vec![
+            (INTERNAL_ERROR, Internal, "internal error"),
+            (FILE_NOT_FOUND, NotFound, "IO error"),
+            (PERMISSION_DENIED, Io, "IO error"),
+            (TOO_MANY_OPEN_FILES, Io, "IO error"),
+            (FILE_TOO_LARGE, TooLarge, "IO error"),
+            (FILE_ALREADY_EXISTS, Io, "IO error"),
+            (FILE_BUSY, Io, "IO error"),
+            (FILE_IS_DIRECTORY, Io, "IO error"),
+            (FILE_SIZE_EXCEEDED, TooLarge, "IO error"),
+            (FILE_CLOSED, Io, "IO error"),
+            (OUT_OF_DISK, Io, "IO error"),
+            (UNEXPECTED_EOF, Corrupt, "unexpected end-of-file"),
+            (MMAP_FAILED, Io, "failed to mmap()"),
+            (WRONG_LOG_MAGIC_NUMBER, WrongType, "wrong log magic number"),
+            (
+                WRONG_LOG_MAJOR_VERSION,
+                WrongType,
+                "wrong log major version",
+            ),
+            (
+                UNSUPPORTED_LOG_MINOR_VERSION,
+                WrongType,
+                "unsupported log minor version",
+            ),
+            (LOG_TOO_SMALL, Corrupt, "log too small"),
+            (LOG_CLOSED, InvalidState, "log closed"),
+            (LOG_ITERATOR_INACTIVE, InvalidState, "log iterator inactive"),
+            (LOG_ITERATOR_MISMATCH, InvalidState, "log iterator mismatch"),
+            (LOG_ITERATOR_CLOSED, InvalidState, "log iterator closed"),
+            (LOG_HEADER_CORRUPT, Corrupt, "log header corrupt"),
+            (
+                INVALID_COMPRESSION_BLOCK_SIZE,
+                InvalidInput,
+                "invalid compression block size",
+            ),
+            (
+                INVALID_COMPRESSION_TYPE,
+                InvalidInput,
+                "invalid compression type",
+            ),
+            (
+                WRONG_HASH_MAGIC_NUMBER,
+                WrongType,
+                "wrong hash magic number",
+            ),
+            (
+                WRONG_HASH_MAJOR_VERSION,
+                WrongType,
+                "wrong hash major version",
+            ),
+            (
+                UNSUPPORTED_HASH_MINOR_VERSION,
+                WrongType,
+                "unsupported hash minor version",
+            ),
+            (HASH_TOO_SMALL, Corrupt, "hash too small"),
+            (HASH_CLOSED, InvalidState, "hash closed"),
+            (
+                FILE_IDENTIFIER_MISMATCH,
+                WrongType,
+                "file identifier mismatch",
+            ),
+            (HASH_HEADER_CORRUPT, Corrupt, "hash header corrupt"),
+            (HASH_SIZE_INVALID, InvalidInput, "hash size invalid"),
+        ];
+
+        for (code, kind, display) in codes {
+            let error = error::Error::from(code);
+            assert_eq!(kind, error.kind(), "{:?}", code);
+            assert_eq!(display, error.to_string(), "{:?}", code);
+        }
+
+        match log::Reader::open("/nonexistent/sparkey.spl") {
+            Err(e) => assert_eq!(NotFound, e.kind()),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn path_contains_nul() {
+        use failure::Fail;
+
+        let error = match log::Reader::open("/tmp/spar\0key.spl") {
+            Err(e) => e,
+            Ok(_) => panic!("expected an error"),
+        };
+
+        assert_eq!(error::ErrorKind::InvalidInput, error.kind());
+        assert!(
+            error.to_string().contains("/tmp/spar\\0key.spl"),
+            "{}",
+            error
+        );
+        assert!(error.to_string().contains("position 9"), "{}", error);
+
+        let cause = error.cause().unwrap();
+        assert!(cause.downcast_ref::<std::ffi::NulError>().is_some());
+    }
+
+    #[test]
+    fn entry_count() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("count.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.delete(b"a").unwrap();
+        writer.put(b"a", b"3").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let entries = reader.entries().unwrap().count() as u64;
+
+        assert_eq!(entries, reader.entry_count());
+        assert_eq!(3, reader.put_count());
+        assert_eq!(1, reader.delete_count());
+
+        // Entries appended after the reader was opened are neither iterated nor counted.
+        let mut writer = log::Writer::append(&path).unwrap();
+        writer.delete(b"b").unwrap();
+        writer.close().unwrap();
+        assert_eq!(entries, reader.entries().unwrap().count() as u64);
+        assert_eq!(entries, reader.entry_count());
+        assert_eq!(1, reader.delete_count());
+        assert_eq!(5, log::Reader::open(&path).unwrap().entry_count());
+    }
+
+    #[test]
+    fn append_checked() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("append.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.close().unwrap();
+
+        match log::Writer::append_checked(&path, log::CompressionType::None) {
+            Err(error::Error::CompressionTypeMismatch {
+                expected: log::CompressionType::None,
+                actual: log::CompressionType::Snappy,
+            }) => {}
+            other => panic!("expected CompressionTypeMismatch, got {:?}", other),
+        }
+
+        let mut writer = log::Writer::append_checked(&path, log::CompressionType::Snappy).unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.close().unwrap();
+
+        assert_eq!(2, log::Reader::open(&path).unwrap().entry_count());
+    }
+
+    #[test]
+    fn stats() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("stats.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 64).unwrap();
+        writer.put(b"a", b"12").unwrap();
+        writer.put(b"bb", b"1234").unwrap();
+        writer.put(b"a", b"123456").unwrap();
+        writer.delete(b"bb").unwrap();
+        writer.put(b"ccc", b"").unwrap();
+        writer.delete(b"dddd").unwrap();
+        writer.close().unwrap();
+
+        let stats = log::Reader::open(&path).unwrap().stats().unwrap();
+
+        assert_eq!(
+            log::LogStats {
+                num_puts: 4,
+                num_deletes: 2,
+                num_live_keys: 2,
+                total_key_len: 1 + 2 + 1 + 2 + 3 + 4,
+                total_value_len: 2 + 4 + 6,
+                min_value_len: Some(0),
+                max_value_len: Some(6),
+            },
+            stats
+        );
+        assert_eq!(Some(3.0), stats.mean_value_len());
+        assert_eq!(None, log::LogStats::default().mean_value_len());
+    }
+
+    #[test]
+    fn entries_shared() {
+        let value = vec![b'v'; 100];
+        let builder = log::Builder::try_from_iter(vec![(b"key".to_vec(), value.clone())]).unwrap();
+        let reader = builder.reader().unwrap();
+
+        let entries = reader
+            .entries_shared()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(1, entries.len());
+
+        let entry = &entries[0];
+        let clone = entry.clone();
+        assert_eq!(log::EntryType::Put, clone.entry_type);
+        assert_eq!(&b"key"[..], &clone.key[..]);
+        assert_eq!(&value[..], &clone.value[..]);
+        assert_eq!(entry.value.as_ptr(), clone.value.as_ptr());
+    }
+
+    #[test]
+    fn buf_writer() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("buffered.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.set_flush_interval(Some(10));
+        let mut writer = log::BufWriter::new(writer, 100);
+        for i in 0..1050 {
+            writer
+                .put(format!("key{}", i).as_bytes(), b"value")
+                .unwrap();
+        }
+        writer.delete(b"key0").unwrap();
+
+        assert_eq!(10, writer.batches());
+        assert_eq!(10, writer.get_ref().flush_count());
+        assert_eq!(51, writer.queued());
+        assert_eq!(1000, log::Reader::open(&path).unwrap().entry_count());
+        let writer = writer.into_inner().unwrap();
+        assert_eq!(11, writer.flush_count());
+        assert_eq!(Some(10), writer.flush_interval());
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        assert_eq!(1050, reader.put_count());
+        assert_eq!(1, reader.delete_count());
+
+        // The same entries written directly, flushing every 10 entries.
+        let path = dir.path().join("unbuffered.spl");
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.set_flush_interval(Some(10));
+        for i in 0..1050 {
+            writer
+                .put(format!("key{}", i).as_bytes(), b"value")
+                .unwrap();
+        }
+        writer.delete(b"key0").unwrap();
+        assert_eq!(105, writer.flush_count());
+        writer.close().unwrap();
+
+        let path = dir.path().join("dropped.spl");
+        let writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        let mut writer = log::BufWriter::new(writer, 100);
+        writer.put(b"key", b"value").unwrap();
+        drop(writer);
+
+        assert_eq!(1, log::Reader::open(&path).unwrap().entry_count());
+    }
+
+    // `BytesMut` hashes and compares by content only.
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn to_hashmap() {
+        use std::convert::TryFrom;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("hashmap");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.put(b"a", b"3").unwrap();
+        writer.delete(b"b").unwrap();
+        writer.put(b"c", b"4").unwrap();
+        let map = writer.close().unwrap();
+
+        let hashmap = map.hash_reader().to_hashmap().unwrap();
+        let mut pairs = hashmap
+            .iter()
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect::<Vec<_>>();
+        pairs.sort();
+
+        assert_eq!(
+            vec![
+                (b"a".to_vec(), b"3".to_vec()),
+                (b"c".to_vec(), b"4".to_vec()),
+            ],
+            pairs
+        );
+        assert_eq!(
+            hashmap,
+            collections::HashMap::try_from(map.hash_reader()).unwrap()
+        );
+    }
+
+    #[test]
+    fn iter_state() {
+        let builder: log::Builder = vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), b"2".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+        let reader = builder.reader().unwrap();
+        let mut keys = reader.keys().unwrap();
+
+        assert_eq!(log::IterState::New, keys.state());
+        keys.next().unwrap().unwrap();
+        assert_eq!(log::IterState::Active, keys.state());
+        keys.next().unwrap().unwrap();
+        assert_eq!(log::IterState::Active, keys.state());
+        assert!(keys.next().is_none());
+        assert_eq!(log::IterState::Closed, keys.state());
+
+        keys.reset().unwrap();
+        assert_eq!(log::IterState::New, keys.state());
+        assert!(keys.next().is_some());
+        assert_eq!(log::IterState::Active, keys.state());
+    }
+
+    #[test]
+    fn from_raw_checked() {
+        use std::ptr;
+
+        fn assert_null_pointer<A>(result: error::Result<A>) {
+            match result {
+                Err(error::Error::NullPointer) => {}
+                Err(e) => panic!("expected NullPointer, got {:?}", e),
+                Ok(_) => panic!("expected NullPointer"),
+            }
+        }
+
+        let builder: log::Builder = vec![(b"a".to_vec(), b"1".to_vec())].into_iter().collect();
+        let reader = builder.reader().unwrap();
+
+        unsafe {
+            assert_null_pointer(log::Reader::from_raw_checked(ptr::null_mut()));
+            assert_null_pointer(log::Writer::from_raw_checked(ptr::null_mut()));
+            assert_null_pointer(log::Entries::from_raw_checked(
+                ptr::null_mut(),
+                &reader,
+                None,
+            ));
+            assert_null_pointer(log::Keys::from_raw_checked(ptr::null_mut(), &reader, None));
+            assert_null_pointer(log::Values::from_raw_checked(
+                ptr::null_mut(),
+                &reader,
+                None,
+            ));
+
+            let raw = log::Reader::from_raw_checked(reader.as_raw()).unwrap();
+            assert_eq!(1, raw.entries().unwrap().count());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn from_fd() {
+        use std::os::unix::io::AsRawFd;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("fd.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.close().unwrap();
+
+        let file = fs::File::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let reader = log::Reader::from_fd(file.as_raw_fd()).unwrap();
+        drop(file);
+
+        let entries = reader
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(&b"value"[..], &entries[0].value[..]);
+        assert_eq!(1, reader.header().num_puts);
+        reader.verify().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn from_fd_verify_truncated() {
+        use std::os::unix::io::AsRawFd;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("fd.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        for i in 0..100u8 {
+            writer.put(&[i], &[i; 100]).unwrap();
+        }
+        writer.close().unwrap();
+
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        let reader = log::Reader::from_fd(file.as_raw_fd()).unwrap();
+        file.set_len(file.metadata().unwrap().len() / 2).unwrap();
+
+        match reader.verify() {
+            Err(error::Error::LogTruncated { .. }) => {}
+            other => panic!("expected LogTruncated, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shared_reader() {
+        use std::thread;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("shared");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 512).unwrap();
+        for i in 0..1000 {
+            writer
+                .put(
+                    format!("key{}", i).as_bytes(),
+                    format!("value{}", i).as_bytes(),
+                )
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let reader = hash::SharedReader::new(
+            hash::Reader::open(base.with_extension("spi"), base.with_extension("spl")).unwrap(),
+        );
+
+        let threads = (0..8)
+            .map(|t| {
+                let reader = reader.clone();
+                thread::spawn(move || {
+                    for round in 0..10 {
+                        for i in (t + round..1000).step_by(7) {
+                            let value = reader.get(format!("key{}", i).as_bytes()).unwrap();
+                            assert_eq!(format!("value{}", i).as_bytes(), &value.unwrap()[..]);
+                        }
+                        assert!(!reader.contains_key(b"absent").unwrap());
+                    }
+                    reader.iter().unwrap().count()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            assert_eq!(1000, thread.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn display_entry_type() {
+        assert_eq!("put", log::EntryType::Put.to_string());
+        assert_eq!("delete", log::EntryType::Delete.to_string());
+    }
+
+    #[test]
+    fn puts_and_deletes_only() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("mixed.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.delete(b"a").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.delete(b"c").unwrap();
+        writer.put(b"a", b"3").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+
+        let puts = reader
+            .entries()
+            .unwrap()
+            .puts_only()
+            .map(|p| p.map(|(k, v)| (k.to_vec(), v.to_vec())))
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+                (b"a".to_vec(), b"3".to_vec()),
+            ],
+            puts
+        );
+
+        let deletes = reader
+            .entries()
+            .unwrap()
+            .deletes_only()
+            .map(|k| k.map(|k| k.to_vec()))
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![b"a".to_vec(), b"c".to_vec()], deletes);
+    }
+
+    #[test]
+    fn delete_if_present() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("delete");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"present", b"1").unwrap();
+        writer.put(b"deleted", b"2").unwrap();
+        writer.delete(b"deleted").unwrap();
+        let map = writer.close().unwrap();
+
+        let mut writer = log::Writer::append(base.with_extension("spl")).unwrap();
+        assert!(writer
+            .delete_if_present(map.hash_reader(), b"present")
+            .unwrap());
+        assert!(!writer
+            .delete_if_present(map.hash_reader(), b"deleted")
+            .unwrap());
+        assert!(!writer
+            .delete_if_present(map.hash_reader(), b"absent")
+            .unwrap());
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(base.with_extension("spl")).unwrap();
+        assert_eq!(2, reader.delete_count());
+    }
+
+    #[test]
+    fn get_mmap() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let plain = dir.path().join("plain");
+        let snappy = dir.path().join("snappy");
+
+        for (base, compression_type) in &[
+            (&plain, log::CompressionType::None),
+            (&snappy, log::CompressionType::Snappy),
+        ] {
+            let mut writer = map::Map::create(base, *compression_type, 1024).unwrap();
+            writer.put(b"key", &[7; 4000]).unwrap();
+            writer.put(b"empty", b"").unwrap();
+            writer.close().unwrap();
+        }
+
+        let map = map::Map::open(&plain).unwrap();
+        let first = map.hash_reader().get_mmap(b"key").unwrap().unwrap();
+        let second = map.hash_reader().get_mmap(b"key").unwrap().unwrap();
+
+        assert_eq!(&[7; 4000][..], &first[..]);
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        assert!(map
+            .hash_reader()
+            .get_mmap(b"empty")
+            .unwrap()
+            .unwrap()
+            .is_empty());
+        assert!(map.hash_reader().get_mmap(b"absent").unwrap().is_none());
+
+        let map = map::Map::open(&snappy).unwrap();
+        match map.hash_reader().get_mmap(b"key") {
+            Err(error::Error::LogCompressed) => {}
+            other => panic!("expected LogCompressed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hash_header() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log_path = dir.path().join("header.spl");
+        let hash_path = dir.path().join("header.spi");
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"short", b"a much longer value").unwrap();
+        writer.put(b"a longer key", b"v").unwrap();
+        writer.put(b"short", b"").unwrap();
+        writer.close().unwrap();
+
+        hash::write(&hash_path, &log_path, hash::HashSize::Bits64).unwrap();
+        let reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+
+        assert_eq!(12, reader.max_key_len());
+        assert_eq!(19, reader.max_value_len());
+        assert_eq!(hash::HashSize::Bits64, reader.hash_size().unwrap());
+
+        let header = reader.header().unwrap();
+        assert_eq!(12, header.max_key_len);
+        assert_eq!(19, header.max_value_len);
+        assert_eq!(3, header.num_puts);
+        assert_eq!(2, header.num_entries);
+        assert_eq!(
+            reader.log_reader().header().file_identifier,
+            header.file_identifier
+        );
+
+        // The reader keeps describing the index it has open after the file is replaced.
+        hash::write_atomic(&hash_path, &log_path, hash::HashSize::Bits32).unwrap();
+        assert_eq!(hash::HashSize::Bits64, reader.hash_size().unwrap());
+        assert_eq!(header, reader.header().unwrap());
+        let reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+        assert_eq!(hash::HashSize::Bits32, reader.hash_size().unwrap());
+    }
+
+    #[test]
+    fn database() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("database");
+
+        let mut writer = map::Database::create(&base, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.finalize().unwrap();
+
+        let map = map::Database::open(&base).unwrap();
+        assert_eq!(&b"value"[..], &map.get(b"key").unwrap().unwrap()[..]);
+        assert!(!dir.path().join("database.spi.tmp").exists());
+
+        let unfinished = dir.path().join("unfinished");
+        let mut writer = map::Database::create(&unfinished, log::CompressionType::None, 0).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        drop(writer);
+
+        assert!(unfinished.with_extension("spl").exists());
+        match map::Database::open(&unfinished) {
+            Err(e) => assert_eq!(error::ErrorKind::NotFound, e.kind()),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn write_atomic() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log_path = dir.path().join("atomic.spl");
+        let hash_path = dir.path().join("atomic.spi");
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"old", b"1").unwrap();
+        writer.close().unwrap();
+        hash::write_atomic(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+
+        let old_reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+
+        let mut writer = log::Writer::append(&log_path).unwrap();
+        writer.put(b"new", b"2").unwrap();
+        writer.close().unwrap();
+        hash::write_atomic(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+
+        assert!(!dir.path().join("atomic.spi.tmp").exists());
+
+        assert_eq!(&b"1"[..], &old_reader.get(b"old").unwrap().unwrap()[..]);
+        assert_eq!(None, old_reader.get(b"new").unwrap());
+        assert_eq!(1, old_reader.len());
+
+        let new_reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+        assert_eq!(&b"1"[..], &new_reader.get(b"old").unwrap().unwrap()[..]);
+        assert_eq!(&b"2"[..], &new_reader.get(b"new").unwrap().unwrap()[..]);
+        assert_eq!(2, new_reader.len());
+    }
+
+    #[test]
+    fn hash_compression_info() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("compression");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 2048).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        let map = writer.close().unwrap();
+
+        let reader = map.hash_reader();
+        assert_eq!(log::CompressionType::Snappy, reader.compression_type());
+        assert_eq!(2048, reader.compression_block_size());
+        assert_eq!(
+            log::CompressionType::Snappy,
+            reader.log_reader().compression_type()
+        );
+        assert_eq!(2048, reader.log_reader().compression_block_size());
+    }
+
+    #[test]
+    fn filter_into() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+
+        let mut writer = map::Map::create(&src, log::CompressionType::None, 0).unwrap();
+        for b in 0..10u8 {
+            writer.put(&[b, b'x'], &[b]).unwrap();
+        }
+        writer.put(&[2, b'x'], b"updated").unwrap();
+        writer.delete(&[4, b'x']).unwrap();
+        writer.close().unwrap();
+
+        let mut seen = 0;
+        map::filter_into(&src, &dst, log::CompressionType::Snappy, 1024, |key, _| {
+            seen += 1;
+            key[0] % 2 == 0
+        })
+        .unwrap();
+        let filtered = map::Map::open(&dst).unwrap();
+
+        assert_eq!(9, seen);
+        assert_eq!(4, filtered.len());
+        assert_eq!(
+            &b"updated"[..],
+            &filtered.get(&[2, b'x']).unwrap().unwrap()[..]
+        );
+        assert!(filtered.contains_key(&[0, b'x']).unwrap());
+        assert!(!filtered.contains_key(&[4, b'x']).unwrap());
+        assert!(!filtered.contains_key(&[1, b'x']).unwrap());
+        assert_eq!(4, filtered.hash_reader().log_reader().entry_count());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn put_many() {
+        use std::os::unix::io::AsRawFd;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let keys = (0..10_000).map(|i| format!("key{}", i)).collect::<Vec<_>>();
+        let value = [b'v'; 256];
+        let pairs = keys
+            .iter()
+            .map(|k| (k.as_bytes(), &value[..]))
+            .collect::<Vec<_>>();
+
+        let path = dir.path().join("many.spl");
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put_many(&pairs).unwrap();
+        writer.close().unwrap();
+        assert_eq!(10_000, log::Reader::open(&path).unwrap().put_count());
+
+        // Swap the writer's file for /dev/full so that a put fails once its 1 MiB buffer is flushed.
+        let path = dir.path().join("full.spl");
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        let full = fs::File::create("/dev/full").unwrap();
+        let fd = fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .find(|p| fs::read_link(p).is_ok_and(|target| target == path))
+            .unwrap();
+        let fd = fd.file_name().unwrap().to_str().unwrap().parse().unwrap();
+        assert_eq!(fd, unsafe { libc::dup2(full.as_raw_fd(), fd) });
+
+        match writer.put_many(&pairs) {
+            Err(error::Error::BatchFailed { index, error }) => {
+                assert!(index > 0 && index < pairs.len(), "{}", index);
+                assert_eq!(error::ErrorKind::Io, error.kind());
+            }
+            other => panic!("expected BatchFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("verify.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        for i in 0..1000 {
+            writer
+                .put(format!("key{}", i).as_bytes(), &[b'v'; 100])
+                .unwrap();
+        }
+        writer.delete(b"key0").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        reader.verify().unwrap();
+
+        // Appending doesn't invalidate readers opened before.
+        let mut writer = log::Writer::append(&path).unwrap();
+        writer.put(b"key1000", b"v").unwrap();
+        writer.close().unwrap();
+        reader.verify().unwrap();
+
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len / 2)
+            .unwrap();
+
+        match reader.verify() {
+            Err(e @ error::Error::LogTruncated { .. }) => {
+                assert_eq!(error::ErrorKind::Corrupt, e.kind())
+            }
+            other => panic!("expected LogTruncated, got {:?}", other),
+        }
+        // A log that is already truncated can't be opened at all.
+        match log::Reader::open(&path) {
+            Err(error::Error::LogTooSmall) => {}
+            other => panic!("expected LogTooSmall, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn writer_config() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let config = log::WriterConfig::default().snappy(4096);
+        assert_eq!(log::WriterConfig::default(), config.uncompressed(),);
+
+        for name in &["a.spl", "b.spl"] {
+            let path = dir.path().join(name);
+            let mut writer = log::Writer::create_with(&path, config).unwrap();
+            writer.put(b"key", b"value").unwrap();
+            writer.close().unwrap();
+
+            let reader = log::Reader::open(&path).unwrap();
+            assert_eq!(log::CompressionType::Snappy, reader.compression_type());
+            assert_eq!(4096, reader.compression_block_size());
+        }
+
+        match log::Writer::create_with(
+            dir.path().join("c.spl"),
+            log::WriterConfig::default().snappy(1),
+        ) {
+            Err(error::Error::CompressionBlockSizeTooSmall { .. }) => {}
+            other => panic!(
+                "expected CompressionBlockSizeTooSmall, got {:?}",
+                other.err()
+            ),
+        }
+    }
+
+    #[cfg(feature = "ndjson")]
+    #[test]
+    fn export_ndjson() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("export");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"text", b"value").unwrap();
+        writer.put(&[0xff, 0], &[0xfe]).unwrap();
+        writer.put(b"deleted", b"value").unwrap();
+        writer.delete(b"deleted").unwrap();
+        let map = writer.close().unwrap();
+
+        for &encoding in &[
+            log::NdjsonEncoding::Base64,
+            log::NdjsonEncoding::Utf8IfValid,
+        ] {
+            let mut out = Vec::new();
+            map.hash_reader().export_ndjson(&mut out, encoding).unwrap();
+
+            let records = str::from_utf8(&out)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(2, records.len());
+
+            let decode = |record: &serde_json::Value, field: &str| match &record[field] {
+                serde_json::Value::String(s) => base64::decode(s).unwrap(),
+                _ => record[format!("{}_utf8", field)]
+                    .as_str()
+                    .unwrap()
+                    .as_bytes()
+                    .to_vec(),
+            };
+            let pairs = records
+                .iter()
+                .map(|record| {
+                    assert_eq!("put", record["type"]);
+                    (decode(record, "key"), decode(record, "value"))
+                })
+                .collect::<collections::HashMap<_, _>>();
+
+            assert_eq!(Some(&b"value".to_vec()), pairs.get(&b"text"[..]));
+            assert_eq!(Some(&vec![0xfe]), pairs.get(&[0xff, 0][..]));
+
+            let text = records
+                .iter()
+                .find(|record| record["type"] == "put" && decode(record, "key") == b"text")
+                .unwrap();
+            match encoding {
+                log::NdjsonEncoding::Base64 => assert_eq!("dGV4dA==", text["key"]),
+                log::NdjsonEncoding::Utf8IfValid => assert_eq!("text", text["key_utf8"]),
+            }
+        }
+    }
+
+    #[cfg(feature = "ndjson")]
+    #[test]
+    fn import_ndjson() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+
+        let mut writer = map::Map::create(&src, log::CompressionType::Snappy, 1024).unwrap();
+        for i in 0..100 {
+            writer
+                .put(format!("key{}", i).as_bytes(), &[i as u8, 0xff])
+                .unwrap();
+        }
+        writer.put("ключ".as_bytes(), b"value").unwrap();
+        let src = writer.close().unwrap();
+
+        let mut out = Vec::new();
+        src.hash_reader()
+            .export_ndjson(&mut out, log::NdjsonEncoding::Utf8IfValid)
+            .unwrap();
+        out.extend_from_slice(b"\n{\"key_utf8\":\"key7\",\"delete\":true}\n");
+
+        let mut writer = map::Map::create(&dst, log::CompressionType::None, 0).unwrap();
+        assert_eq!(102, writer.log_writer().import_ndjson(&out[..]).unwrap());
+        let dst = writer.close().unwrap();
+
+        assert_eq!(100, dst.len());
+        assert_eq!(None, dst.get(b"key7").unwrap());
+        for pair in src.iter().unwrap() {
+            let (key, value) = pair.unwrap();
+            if &key[..] != b"key7" {
+                assert_eq!(Some(value), dst.get(&key).unwrap());
+            }
+        }
+
+        let mut writer =
+            log::Writer::create(dir.path().join("bad.spl"), log::CompressionType::None, 0).unwrap();
+        let input = "{\"key\":\"a2V5\",\"value\":\"dmFsdWU=\"}\n\n{\"key\":\"a2V5\"}\n";
+        match writer.import_ndjson(input.as_bytes()) {
+            Err(e @ error::Error::InvalidRecord { line: 3, .. }) => {
+                assert_eq!(error::ErrorKind::InvalidInput, e.kind());
+                assert!(e.to_string().contains("missing `value`"), "{}", e);
+            }
+            other => panic!("expected InvalidRecord, got {:?}", other),
+        }
+        match writer.import_ndjson(&b"not json"[..]) {
+            Err(error::Error::InvalidRecord { line: 1, .. }) => {}
+            other => panic!("expected InvalidRecord, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+
+        let pairs = [
+            ("plain", "value"),
+            ("comma,key", "a, b, c"),
+            ("quote", "say \"hi\""),
+            ("lines", "one\r\ntwo\nthree"),
+            ("empty", ""),
+        ];
+        let mut writer = map::Map::create(&src, log::CompressionType::None, 0).unwrap();
+        for (key, value) in &pairs {
+            writer.put(key.as_bytes(), value.as_bytes()).unwrap();
+        }
+        let src = writer.close().unwrap();
+
+        let mut out = Vec::new();
+        src.hash_reader().export_csv(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\"comma,key\",\"a, b, c\"\r\n"), "{}", out);
+        assert!(out.contains("quote,\"say \"\"hi\"\"\"\r\n"), "{}", out);
+        assert!(out.contains("plain,value\r\n"), "{}", out);
+
+        let input = format!("{}\r\nplain,,delete\r\n\r\nadded,new,\n", out);
+        let mut writer = map::Map::create(&dst, log::CompressionType::None, 0).unwrap();
+        assert_eq!(7, writer.log_writer().import_csv(input.as_bytes()).unwrap());
+        let dst = writer.close().unwrap();
+
+        assert_eq!(5, dst.len());
+        assert_eq!(None, dst.get(b"plain").unwrap());
+        assert_eq!(&b"new"[..], &dst.get(b"added").unwrap().unwrap()[..]);
+        for (key, value) in &pairs[1..] {
+            assert_eq!(
+                value.as_bytes(),
+                &dst.get(key.as_bytes()).unwrap().unwrap()[..]
+            );
+        }
+
+        let mut writer =
+            log::Writer::create(dir.path().join("bad.spl"), log::CompressionType::None, 0).unwrap();
+        match writer.import_csv(&b"a,b\n\"multi\nline\",x\"y\n"[..]) {
+            Err(error::Error::InvalidRecord { line: 2, .. }) => {}
+            other => panic!("expected InvalidRecord, got {:?}", other),
+        }
+        match writer.import_csv(&b"a,b\nkey,value,maybe\n"[..]) {
+            Err(error::Error::InvalidRecord { line: 2, .. }) => {}
+            other => panic!("expected InvalidRecord, got {:?}", other),
+        }
+
+        let base = dir.path().join("binary");
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(&[0xff], b"value").unwrap();
+        let binary = writer.close().unwrap();
+        match binary.hash_reader().export_csv(Vec::new()) {
+            Err(e @ error::Error::InvalidUtf8 { field: "key", .. }) => {
+                assert_eq!(error::ErrorKind::InvalidInput, e.kind())
+            }
+            other => panic!("expected InvalidUtf8, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn get_into() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("get_into");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 512).unwrap();
+        for i in 0..100usize {
+            writer
+                .put(format!("key{}", i).as_bytes(), &vec![b'v'; i * 10])
+                .unwrap();
+        }
+        let map = writer.close().unwrap();
+        let reader = map.hash_reader();
+
+        let mut buf = Vec::new();
+        assert_eq!(Some(0), reader.get_into(b"key0", &mut buf).unwrap());
+        let capacity = buf.capacity();
+        assert!(capacity >= 990);
+
+        for i in (0..100usize).rev() {
+            let len = reader
+                .get_into(format!("key{}", i).as_bytes(), &mut buf)
+                .unwrap();
+            assert_eq!(Some(i * 10), len);
+            assert_eq!(vec![b'v'; i * 10], buf);
+            assert_eq!(capacity, buf.capacity());
+        }
+
+        assert_eq!(None, reader.get_into(b"missing", &mut buf).unwrap());
+        assert!(buf.is_empty());
+        assert_eq!(capacity, buf.capacity());
+    }
+
+    #[test]
+    fn map_hash_size() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+
+        for &(name, hash_size, expected) in &[
+            ("auto", hash::HashSize::Auto, hash::HashSize::Bits32),
+            ("bits64", hash::HashSize::Bits64, hash::HashSize::Bits64),
+        ] {
+            let mut writer =
+                map::Map::create(dir.path().join(name), log::CompressionType::None, 0).unwrap();
+            writer.set_hash_size(hash_size);
+            writer.put(b"key", b"value").unwrap();
+            let map = writer.close().unwrap();
+
+            assert_eq!(expected, map.hash_reader().hash_size().unwrap());
+            assert_eq!(&b"value"[..], &map.get(b"key").unwrap().unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn open_with_hash() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+
+        for base in &[&a, &b] {
+            let mut writer = map::Map::create(base, log::CompressionType::None, 0).unwrap();
+            writer.put(b"key", b"value").unwrap();
+            writer.close().unwrap();
+        }
+
+        let reader =
+            log::Reader::open_with_hash(a.with_extension("spl"), a.with_extension("spi")).unwrap();
+        assert_eq!(&b"value"[..], &reader.get(b"key").unwrap().unwrap()[..]);
+
+        match log::Reader::open_with_hash(a.with_extension("spl"), b.with_extension("spi")) {
+            Err(e @ error::Error::FileIdentifierMismatch) => {
+                assert_eq!(error::ErrorKind::WrongType, e.kind())
+            }
+            other => panic!("expected FileIdentifierMismatch, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn entry_offsets() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+
+        for &(name, compression_type) in &[
+            ("none.spl", log::CompressionType::None),
+            ("snappy.spl", log::CompressionType::Snappy),
+        ] {
+            let path = dir.path().join(name);
+            let mut writer = log::Writer::create(&path, compression_type, 256).unwrap();
+            for i in 0..200 {
+                writer
+                    .put(format!("key{}", i).as_bytes(), &vec![b'v'; i % 50])
+                    .unwrap();
+            }
+            writer.put(b"long", &[b'v'; 1000]).unwrap();
+            writer.delete(b"key0").unwrap();
+            writer.close().unwrap();
+
+            let reader = log::Reader::open(&path).unwrap();
+            let mut entries = reader.entries().unwrap();
+            assert_eq!(None, entries.offset());
+
+            let mut offsets = Vec::new();
+            while let Some(entry) = entries.next() {
+                entry.unwrap();
+                offsets.push(entries.offset().unwrap());
+            }
+            assert_eq!(None, entries.offset());
+
+            assert_eq!(202, offsets.len());
+            assert_eq!(84, offsets[0].block_position);
+            assert!(offsets.windows(2).all(|w| w[0] < w[1]));
+            match compression_type {
+                log::CompressionType::None => assert!(offsets.iter().all(|o| o.entry_index == 0)),
+                log::CompressionType::Snappy => assert!(offsets.iter().any(|o| o.entry_index > 0)),
+            }
+        }
+    }
+
+    #[test]
+    fn seek_offset() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+
+        for &(name, compression_type) in &[
+            ("none.spl", log::CompressionType::None),
+            ("snappy.spl", log::CompressionType::Snappy),
+        ] {
+            let path = dir.path().join(name);
+            let mut writer = log::Writer::create(&path, compression_type, 256).unwrap();
+            for i in 0..100 {
+                writer
+                    .put(format!("key{}", i).as_bytes(), &vec![b'v'; i % 30])
+                    .unwrap();
+            }
+            writer.put(b"long", &[b'v'; 1000]).unwrap();
+            writer.put(b"last", b"value").unwrap();
+            writer.close().unwrap();
+
+            let reader = log::Reader::open(&path).unwrap();
+            let mut entries = reader.entries().unwrap();
+            let mut index = collections::HashMap::new();
+            while let Some(entry) = entries.next() {
+                index.insert(entry.unwrap().key.to_vec(), entries.offset().unwrap());
+            }
+
+            for key in &[&b"key42"[..], b"key0", b"long", b"last", b"key99"] {
+                entries.seek_offset(index[*key]).unwrap();
+                let entry = entries.next().unwrap().unwrap();
+                assert_eq!(key, &&entry.key[..]);
+            }
+            entries.seek_offset(index[&b"key7"[..]]).unwrap();
+            let next = entries.nth(1).unwrap().unwrap();
+            assert_eq!(&b"key8"[..], &next.key[..]);
+
+            let long = index[&b"long"[..]];
+            let bad = match compression_type {
+                log::CompressionType::None => log::Offset {
+                    block_position: long.block_position + 500,
+                    entry_index: 0,
+                },
+                log::CompressionType::Snappy => log::Offset {
+                    block_position: index[&b"key0"[..]].block_position,
+                    entry_index: 1000,
+                },
+            };
+            let past_end = log::Offset {
+                block_position: 1 << 40,
+                entry_index: 0,
+            };
+            for &offset in &[bad, past_end] {
+                match entries.seek_offset(offset) {
+                    Err(e @ error::Error::InvalidOffset { .. }) => {
+                        assert_eq!(error::ErrorKind::InvalidInput, e.kind())
+                    }
+                    other => panic!("expected InvalidOffset, got {:?}", other),
+                }
+                assert!(entries.next().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn writer_extend() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("extend.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        writer.extend((0..10u8).map(|i| (vec![b'k', i], vec![i; i as usize])));
+        writer.extend(vec![(b"last".to_vec(), b"value".to_vec())]);
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let entries = reader
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(11, entries.len());
+        for (i, entry) in entries[..10].iter().enumerate() {
+            assert_eq!(&[b'k', i as u8][..], &entry.key[..]);
+            assert_eq!(&vec![i as u8; i][..], &entry.value[..]);
+        }
+        assert_eq!(&b"last"[..], &entries[10].key[..]);
+    }
+
+    #[test]
+    fn empty_keys_and_values() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+
+        for &compression_type in &[log::CompressionType::None, log::CompressionType::Snappy] {
+            let base = dir.path().join(format!("empty-{}", compression_type));
+            let mut writer = map::Map::create(&base, compression_type, 1024).unwrap();
+            writer.put(b"", b"").unwrap();
+            writer.put(b"empty value", b"").unwrap();
+            writer.put(b"", b"empty key").unwrap();
+            writer.put(b"deleted", b"").unwrap();
+            writer.delete(b"deleted").unwrap();
+            let map = writer.close().unwrap();
+
+            let entries = map
+                .hash_reader()
+                .log_reader()
+                .entries()
+                .unwrap()
+                .collect::<error::Result<Vec<_>>>()
+                .unwrap();
+            let pairs = entries
+                .iter()
+                .map(|e| (e.entry_type, &e.key[..], &e.value[..]))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                vec![
+                    (log::EntryType::Put, &b""[..], &b""[..]),
+                    (log::EntryType::Put, b"empty value", b""),
+                    (log::EntryType::Put, b"", b"empty key"),
+                    (log::EntryType::Put, b"deleted", b""),
+                    (log::EntryType::Delete, b"deleted", b""),
+                ],
+                pairs
+            );
+
+            assert_eq!(2, map.len());
+            assert_eq!(&b"empty key"[..], &map.get(b"").unwrap().unwrap()[..]);
+            let value = map.get(b"empty value").unwrap().unwrap();
+            assert!(value.is_empty());
+            assert!(!map.contains_key(b"deleted").unwrap());
+
+            let mut buf = vec![1, 2, 3];
+            assert_eq!(
+                Some(0),
+                map.hash_reader()
+                    .get_into(b"empty value", &mut buf)
+                    .unwrap()
+            );
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn flush_interval() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("flush.spl");
+        let flushed = || log::Reader::open(&path).unwrap().entry_count();
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        assert_eq!(None, writer.flush_interval());
+        writer.set_flush_interval(Some(10));
+
+        for i in 0..25u8 {
+            writer.put(&[i], b"value").unwrap();
+        }
+        // Flushed before the 11th and 21st puts.
+        assert_eq!(20, flushed());
+
+        for i in 0..5u8 {
+            writer.delete(&[i]).unwrap();
+        }
+        assert_eq!(20, flushed());
+        // Deletes count too, so the next entry flushes all 30.
+        writer.delete(&[5]).unwrap();
+        assert_eq!(30, flushed());
+
+        writer.flush().unwrap();
+        assert_eq!(31, flushed());
+        for i in 0..10u8 {
+            writer.put(&[i], b"again").unwrap();
+        }
+        assert_eq!(31, flushed());
+
+        writer.set_flush_interval(None);
+        for i in 0..20u8 {
+            writer.put(&[i], b"again").unwrap();
+        }
+        assert_eq!(31, flushed());
+
+        writer.close().unwrap();
+        assert_eq!(61, flushed());
+    }
+
+    #[test]
+    fn filter_keys() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("filter_keys.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        for i in 0..100 {
+            writer
+                .put(format!("key{}", i).as_bytes(), &[b'v'; 100])
+                .unwrap();
+        }
+        writer.delete(b"key10").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let mut entries = reader
+            .entries()
+            .unwrap()
+            .filter_keys(|key| key.ends_with(b"0"));
+
+        // Keys this short are stored inline, so only the values read allocate.
+        let before = ALLOCATIONS.with(|n| n.get());
+        let mut matched = 0;
+        let mut deletes = 0;
+        for entry in entries.by_ref() {
+            let entry = entry.unwrap();
+            assert!(entry.key.ends_with(b"0"));
+            match entry.entry_type {
+                log::EntryType::Put => assert_eq!(&[b'v'; 100][..], &entry.value[..]),
+                log::EntryType::Delete => deletes += 1,
+            }
+            matched += 1;
+        }
+        let allocations = ALLOCATIONS.with(|n| n.get()) - before;
+
+        assert_eq!(11, matched);
+        assert_eq!(1, deletes);
+        assert_eq!(10, allocations);
+    }
+
+    #[test]
+    fn entry_conveniences() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("entry.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.put(&[0xff], &[0xfe]).unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let mut entries = reader.entries().unwrap();
+
+        let text = entries.next().unwrap().unwrap();
+        assert_eq!(Ok("key"), text.key_str());
+        assert_eq!(Ok("value"), text.value_str());
+        let (key, value) = text.into_pair();
+        assert_eq!(&b"key"[..], &key[..]);
+        assert_eq!(&b"value"[..], &value[..]);
+
+        let binary = entries.next().unwrap().unwrap();
+        assert!(binary.key_str().is_err());
+        assert_eq!(0, binary.value_str().unwrap_err().valid_up_to());
+        assert_eq!((vec![0xff], vec![0xfe]), {
+            let (key, value) = binary.into_pair();
+            (key.to_vec(), value.to_vec())
+        });
+    }
+
+    #[test]
+    fn estimate_hash_size() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+
+        for &compression_type in &[log::CompressionType::None, log::CompressionType::Snappy] {
+            let log_path = dir.path().join(format!("{}.spl", compression_type));
+            let mut writer = log::Writer::create(&log_path, compression_type, 1024).unwrap();
+            for i in 0..1000 {
+                writer
+                    .put(format!("key{}", i).as_bytes(), b"value")
+                    .unwrap();
+            }
+            writer.delete(b"key0").unwrap();
+            writer.close().unwrap();
+
+            for &hash_size in &[
+                hash::HashSize::Auto,
+                hash::HashSize::Bits32,
+                hash::HashSize::Bits64,
+            ] {
+                let hash_path = dir
+                    .path()
+                    .join(format!("{}-{:?}.spi", compression_type, hash_size));
+                let estimate = hash::estimate_size(&log_path, hash_size).unwrap();
+                hash::write(&hash_path, &log_path, hash_size).unwrap();
+
+                assert_eq!(fs::metadata(&hash_path).unwrap().len(), estimate);
+            }
+        }
+    }
+
+    #[test]
+    fn database_readonly_snapshot() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("snapshot");
+
+        let mut writer = map::Database::create(&base, log::CompressionType::Snappy, 1024).unwrap();
+        for i in 0..100 {
+            writer.put(format!("old{}", i).as_bytes(), b"old").unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let snapshot = map::Database::open_readonly(&base).unwrap();
+
+        let writer = {
+            let base = base.clone();
+            thread::spawn(move || {
+                let mut writer = map::Database::append(&base).unwrap();
+                for i in 0..100 {
+                    writer.put(format!("new{}", i).as_bytes(), b"new").unwrap();
+                }
+                writer.delete(b"old0").unwrap();
+                writer.finalize().unwrap();
+            })
+        };
+        let readers = (0..4)
+            .map(|_| {
+                let snapshot = snapshot.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        assert_eq!(100, snapshot.len());
+                        for i in 0..100 {
+                            let key = format!("old{}", i);
+                            assert_eq!(
+                                &b"old"[..],
+                                &snapshot.get(key.as_bytes()).unwrap().unwrap()[..]
+                            );
+                        }
+                        assert!(!snapshot.contains_key(b"new0").unwrap());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(100, snapshot.len());
+        assert!(snapshot.contains_key(b"old0").unwrap());
+
+        let updated = map::Database::open_readonly(&base).unwrap();
+        assert_eq!(199, updated.len());
+        assert!(!updated.contains_key(b"old0").unwrap());
+        assert_eq!(&b"new"[..], &updated.get(b"new99").unwrap().unwrap()[..]);
+    }
+
+    #[test]
+    fn reader_path() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("path");
+        fs::create_dir(dir.path().join("sub")).unwrap();
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        let map = writer.close().unwrap();
+
+        let canonical = fs::canonicalize(base.with_extension("spl")).unwrap();
+        let reader = log::Reader::open(dir.path().join("sub/../path.spl")).unwrap();
+        assert_eq!(Some(canonical.as_path()), reader.path());
+        assert_eq!(
+            Some(base.with_extension("spl").as_path()),
+            map.hash_reader().log_reader().path()
+        );
+
+        let raw = unsafe { log::Reader::from_raw(reader.as_raw()) };
+        assert_eq!(None, raw.path());
+    }
+
+    #[test]
+    fn overflowing_casts() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("casts.spl");
+
+        match log::Writer::create(&path, log::CompressionType::Snappy, u32::MAX) {
+            Err(e @ error::Error::TooLarge { .. }) => {
+                assert_eq!(error::ErrorKind::TooLarge, e.kind())
+            }
+            other => panic!("expected TooLarge, got {:?}", other.err()),
+        }
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        for i in 0..10u8 {
+            writer.put(&[i], &[i]).unwrap();
+        }
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let mut entries = reader.entries().unwrap();
+        for &count in &[i32::MAX as u32 + 1, u32::MAX] {
+            match log::Entries::skip(&mut entries, count) {
+                Err(error::Error::TooLarge { value, max, .. }) => {
+                    assert_eq!(u64::from(count), value);
+                    assert_eq!(i32::MAX as u64, max);
+                }
+                other => panic!("expected TooLarge, got {:?}", other),
+            }
+        }
+
+        // Nothing was skipped by the failed calls.
+        log::Entries::skip(&mut entries, 3).unwrap();
+        assert_eq!(&[3][..], &entries.next().unwrap().unwrap().key[..]);
+
+        let offset = entries.offset().unwrap();
+        match entries.seek_offset(log::Offset {
+            entry_index: i32::MAX as u32 + 1,
+            ..offset
+        }) {
+            Err(error::Error::InvalidOffset { .. }) => {}
+            other => panic!("expected InvalidOffset, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deletions_with_prior() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("deletions.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 1024).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"a", b"2").unwrap();
+        writer.put(b"b", b"3").unwrap();
+        writer.delete(b"a").unwrap();
+        writer.delete(b"never").unwrap();
+        writer.delete(b"a").unwrap();
+        writer.put(b"a", b"4").unwrap();
+        writer.delete(b"a").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let deletions = reader
+            .deletions_with_prior()
+            .unwrap()
+            .map(|d| {
+                let (key, prior) = d.unwrap();
+                (key.to_vec(), prior.map(|v| v.to_vec()))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (b"a".to_vec(), Some(b"2".to_vec())),
+                (b"never".to_vec(), None),
+                (b"a".to_vec(), None),
+                (b"a".to_vec(), Some(b"4".to_vec())),
+            ],
+            deletions
+        );
+    }
+
+    #[test]
+    fn log_reserve() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("reserve.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 64).unwrap();
+        writer.reserve(1 << 20).unwrap();
+        writer.reserve(0).unwrap();
+        for i in 0..100u32 {
+            writer
+                .put(
+                    format!("key{}", i).as_bytes(),
+                    format!("value{}", i).as_bytes(),
+                )
+                .unwrap();
+        }
+        writer.reserve(4096).unwrap();
+        writer.put(b"last", b"entry").unwrap();
+        drop(writer);
+
+        let reader = log::Reader::open(&path).unwrap();
+        reader.verify().unwrap();
+        let entries = reader
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let (k, v) = e.unwrap().into_pair();
+                (k.to_vec(), v.to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(101, entries.len());
+        assert_eq!((b"key0".to_vec(), b"value0".to_vec()), entries[0]);
+        assert_eq!((b"last".to_vec(), b"entry".to_vec()), entries[100]);
+    }
+
+    fn owned_entries(dir: &path::Path) -> error::Result<log::OwnedEntries> {
+        let log_path = dir.join("owned.spl");
+        let hash_path = dir.join("owned.spi");
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::None, 0)?;
+        writer.put(b"a", b"1")?;
+        writer.put(b"b", b"2")?;
+        writer.delete(b"a")?;
+        writer.close()?;
+        hash::write(&hash_path, &log_path, hash::HashSize::Auto)?;
+
+        log::OwnedEntries::entries_from_hash(hash::Reader::open(&hash_path, &log_path)?)
+    }
+
+    #[test]
+    fn log_owned_entries() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+
+        let mut entries = owned_entries(dir.path()).unwrap();
+        assert_eq!(1, entries.hash_reader().len());
+
+        let collected = entries
+            .by_ref()
+            .map(|e| {
+                let e = e.unwrap();
+                (e.entry_type, e.key.to_vec(), e.value.to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![(log::EntryType::Put, b"b".to_vec(), b"2".to_vec())],
+            collected
+        );
+
+        assert!(entries.seek(b"b").unwrap());
+        assert_eq!(&b"b"[..], &entries.next().unwrap().unwrap().key[..]);
+
+        let reader = entries.into_hash_reader();
+        assert_eq!(Some(&b"2"[..]), reader.get(b"b").unwrap().as_deref());
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn log_entry_eq_hash() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("eq.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.put(b"key", b"value").unwrap();
+        writer.put(b"key", b"other").unwrap();
+        writer.delete(b"key").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let entries = reader
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(entries[0], entries[1]);
+        assert_ne!(entries[0], entries[2]);
+        assert_ne!(entries[0], entries[3]);
+
+        let set = entries
+            .into_iter()
+            .take(2)
+            .collect::<collections::HashSet<_>>();
+        assert_eq!(1, set.len());
+    }
+
+    #[test]
+    fn log_iterators_fused() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("fused.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 16).unwrap();
+        for i in 0..5u8 {
+            writer.put(&[i], &[i; 20]).unwrap();
+        }
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+
+        let mut entries = reader.entries().unwrap();
+        assert_eq!(5, entries.by_ref().count());
+        for _ in 0..3 {
+            assert!(entries.next().is_none());
+        }
+        log::Entries::skip(&mut entries, 2).unwrap();
+        assert!(entries.next().is_none());
+
+        let mut keys = reader.keys().unwrap();
+        log::Keys::skip(&mut keys, 3).unwrap();
+        assert_eq!(2, keys.by_ref().count());
+        log::Keys::skip(&mut keys, 10).unwrap();
+        for _ in 0..3 {
+            assert!(keys.next().is_none());
+        }
+
+        let mut values = reader.values().unwrap();
+        log::Values::skip(&mut values, 10).unwrap();
+        for _ in 0..3 {
+            assert!(values.next().is_none());
+        }
+        log::Values::skip(&mut values, 1).unwrap();
+        assert!(values.next().is_none());
+
+        let chained = reader.keys().unwrap().chain(reader.keys().unwrap()).count();
+        assert_eq!(10, chained);
+    }
+
+    #[test]
+    fn map_transcode() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let plain = dir.path().join("plain");
+        let snappy = dir.path().join("snappy");
+        let again = dir.path().join("again");
+
+        let mut writer = map::Map::create(&plain, log::CompressionType::None, 0).unwrap();
+        for i in 0..50u32 {
+            writer
+                .put(format!("key{}", i % 20).as_bytes(), &[i as u8; 40])
+                .unwrap();
+        }
+        writer.delete(b"key3").unwrap();
+        writer.close().unwrap();
+
+        map::transcode(&plain, &snappy, log::CompressionType::Snappy, 128).unwrap();
+        map::transcode(&snappy, &again, log::CompressionType::None, 0).unwrap();
+
+        let entries = |base: &path::Path| {
+            let reader = log::Reader::open(base.with_extension("spl")).unwrap();
+            let entries = reader
+                .entries()
+                .unwrap()
+                .collect::<error::Result<Vec<_>>>()
+                .unwrap();
+            (reader.compression_type(), entries)
+        };
+        let (plain_type, plain_entries) = entries(&plain);
+        let (snappy_type, snappy_entries) = entries(&snappy);
+        let (again_type, again_entries) = entries(&again);
+        assert_eq!(log::CompressionType::None, plain_type);
+        assert_eq!(log::CompressionType::Snappy, snappy_type);
+        assert_eq!(log::CompressionType::None, again_type);
+        assert_eq!(51, plain_entries.len());
+        assert_eq!(plain_entries, snappy_entries);
+        assert_eq!(plain_entries, again_entries);
+
+        let map = map::Map::open(&again).unwrap();
+        assert_eq!(19, map.len());
+        assert_eq!(None, map.get(b"key3").unwrap());
+        assert_eq!(Some(&[49u8; 40][..]), map.get(b"key9").unwrap().as_deref());
+    }
+
+    #[test]
+    fn log_entries_try_clone() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log_path = dir.path().join("clone.spl");
+        let hash_path = dir.path().join("clone.spi");
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::Snappy, 32).unwrap();
+        for i in 0..30u8 {
+            writer.put(&[i % 10], &[i; 7]).unwrap();
+        }
+        writer.delete(&[4]).unwrap();
+        writer.close().unwrap();
+        hash::write(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+
+        let keys =
+            |entries: log::Entries| entries.map(|e| e.unwrap().key.to_vec()).collect::<Vec<_>>();
+
+        let reader = log::Reader::open(&log_path).unwrap();
+        let mut entries = reader.entries().unwrap();
+        assert_eq!(31, keys(entries.try_clone().unwrap()).len());
+        for _ in 0..13 {
+            entries.next().unwrap().unwrap();
+        }
+        let clone = entries.try_clone().unwrap();
+        let tail = keys(clone);
+        assert_eq!(18, tail.len());
+        assert_eq!(tail, keys(entries));
+
+        let hash = hash::Reader::open(&hash_path, &log_path).unwrap();
+        let mut entries = hash.entries().unwrap();
+        assert!(entries.seek(&[7]).unwrap());
+        let clone = entries.try_clone().unwrap();
+        assert_eq!(vec![vec![7], vec![8], vec![9]], keys(clone));
+        entries.next().unwrap().unwrap();
+        let clone = entries.try_clone().unwrap();
+        assert_eq!(keys(clone), keys(entries));
+
+        let mut entries = reader.entries().unwrap();
+        entries.by_ref().count();
+        assert!(entries.try_clone().unwrap().next().is_none());
+    }
+
+    #[test]
+    fn hash_get_or() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("get_or");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"present", b"value").unwrap();
+        writer.put(b"empty", b"").unwrap();
+        writer.put(b"deleted", b"gone").unwrap();
+        writer.delete(b"deleted").unwrap();
+        let map = writer.close().unwrap();
+        let reader = map.hash_reader();
+
+        let fallback = || bytes::BytesMut::from(&b"fallback"[..]);
+        assert_eq!(
+            &b"value"[..],
+            &reader.get_or(b"present", fallback).unwrap()[..]
+        );
+        assert_eq!(&b""[..], &reader.get_or(b"empty", fallback).unwrap()[..]);
+        assert_eq!(
+            &b"fallback"[..],
+            &reader.get_or(b"deleted", fallback).unwrap()[..]
+        );
+        assert_eq!(
+            &b"fallback"[..],
+            &reader.get_or(b"absent", fallback).unwrap()[..]
+        );
+
+        let mut called = false;
+        reader
+            .get_or(b"present", || {
+                called = true;
+                bytes::BytesMut::new()
+            })
+            .unwrap();
+        assert!(!called);
+
+        assert_eq!(
+            &b"value"[..],
+            &reader.get_or_default(b"present").unwrap()[..]
+        );
+        assert!(reader.get_or_default(b"absent").unwrap().is_empty());
+    }
+
+    #[test]
+    fn hash_cursor() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("cursor");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 64).unwrap();
+        for key in &[b"a", b"b", b"c", b"d", b"e"] {
+            writer.put(*key, &key.repeat(3)).unwrap();
+        }
+        writer.delete(b"c").unwrap();
+        writer.put(b"a", b"new").unwrap();
+        let map = writer.close().unwrap();
+
+        let key = |cursor: &hash::Cursor| cursor.current().map(|e| e.key.to_vec());
+
+        let mut cursor = map.hash_reader().cursor().unwrap();
+        assert!(cursor.current().is_none());
+        assert!(cursor.offset().is_none());
+
+        assert!(cursor.seek(b"b").unwrap());
+        let current = cursor.current().unwrap();
+        assert_eq!(log::EntryType::Put, current.entry_type);
+        assert_eq!(&b"bbb"[..], &current.value[..]);
+        let b_offset = cursor.offset().unwrap();
+
+        assert!(cursor.advance().unwrap());
+        assert_eq!(Some(b"d".to_vec()), key(&cursor));
+        assert!(cursor.advance().unwrap());
+        assert_eq!(Some(b"e".to_vec()), key(&cursor));
+        assert!(cursor.advance().unwrap());
+        assert_eq!(Some(b"a".to_vec()), key(&cursor));
+        assert_eq!(&b"new"[..], &cursor.current().unwrap().value[..]);
+        assert!(!cursor.advance().unwrap());
+        assert!(cursor.current().is_none());
+
+        assert!(!cursor.seek(b"c").unwrap());
+        assert!(cursor.current().is_none());
+        assert!(!cursor.advance().unwrap());
+
+        assert!(cursor.seek(b"b").unwrap());
+        assert_eq!(Some(b_offset), cursor.offset());
+
+        cursor.rewind().unwrap();
+        assert!(cursor.current().is_none());
+        assert!(cursor.advance().unwrap());
+        assert_eq!(Some(b"b".to_vec()), key(&cursor));
+    }
+
+    #[derive(Default)]
+    struct CountingPool {
+        free: sync::Mutex<Vec<bytes::BytesMut>>,
+        acquired: sync::atomic::AtomicUsize,
+        reused: sync::atomic::AtomicUsize,
+    }
+
+    impl CountingPool {
+        fn release(&self, buf: bytes::BytesMut) {
+            self.free.lock().unwrap().push(buf);
+        }
+
+        fn acquired(&self) -> usize {
+            self.acquired.load(sync::atomic::Ordering::SeqCst)
+        }
+
+        fn reused(&self) -> usize {
+            self.reused.load(sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl log::BytesPool for CountingPool {
+        fn acquire(&self, len: usize) -> bytes::BytesMut {
+            self.acquired.fetch_add(1, sync::atomic::Ordering::SeqCst);
+            match self.free.lock().unwrap().pop() {
+                Some(buf) => {
+                    if buf.capacity() >= len {
+                        self.reused.fetch_add(1, sync::atomic::Ordering::SeqCst);
+                    }
+                    buf
+                }
+                None => bytes::BytesMut::with_capacity(len),
+            }
+        }
+    }
+
+    #[test]
+    fn log_bytes_pool() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("pool.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 256).unwrap();
+        for i in 0..20u8 {
+            writer.put(&[i; 40], &[i; 100]).unwrap();
+        }
+        writer.delete(&[0; 40]).unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let pool = sync::Arc::new(CountingPool::default());
+
+        let mut entries = reader.entries().unwrap();
+        entries.set_pool(pool.clone());
+        // The pool is shared through an `Arc`, so the iterator can still be sent to other threads.
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&entries);
+        let mut count = 0;
+        for (i, entry) in entries.enumerate() {
+            let entry = entry.unwrap();
+            if i < 20 {
+                assert_eq!(&[i as u8; 40][..], &entry.key[..]);
+                assert_eq!(&[i as u8; 100][..], &entry.value[..]);
+            } else {
+                assert_eq!(log::EntryType::Delete, entry.entry_type);
+                assert!(entry.value.is_empty());
+            }
+            pool.release(entry.value);
+            pool.release(entry.key);
+            count += 1;
+        }
+        assert_eq!(21, count);
+        assert_eq!(42, pool.acquired());
+        // Only the first key and value need new buffers; the values after that reuse the
+        // buffers of earlier values and keys reuse those of earlier keys.
+        assert_eq!(40, pool.reused());
+
+        let mut values = reader.values().unwrap();
+        values.set_pool(pool.clone());
+        let first = values.next().unwrap().unwrap();
+        assert_eq!(&[0; 100][..], &first[..]);
+        assert_eq!(43, pool.acquired());
+    }
+
+    #[test]
+    fn log_write_batch_commit() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("batch.spl");
+        let pairs = |path: &path::Path| {
+            log::Reader::open(path)
+                .unwrap()
+                .entries()
+                .unwrap()
+                .map(|e| {
+                    let e = e.unwrap();
+                    (e.entry_type, e.key.to_vec(), e.value.to_vec())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut batch =
+            log::WriteBatch::new_with(&path, log::WriterConfig::default().snappy(64)).unwrap();
+        batch.put(b"a", b"1").unwrap();
+        batch.put(b"b", b"2").unwrap();
+        assert!(!path.exists());
+        batch.commit().unwrap();
+        assert_eq!(
+            log::CompressionType::Snappy,
+            log::Reader::open(&path).unwrap().compression_type()
+        );
+
+        let mut batch = log::WriteBatch::new(&path).unwrap();
+        batch.delete(b"a").unwrap();
+        batch.put(b"c", b"3").unwrap();
+        assert_eq!(2, pairs(&path).len());
+        batch.commit().unwrap();
+
+        assert_eq!(
+            vec![
+                (log::EntryType::Put, b"a".to_vec(), b"1".to_vec()),
+                (log::EntryType::Put, b"b".to_vec(), b"2".to_vec()),
+                (log::EntryType::Delete, b"a".to_vec(), vec![]),
+                (log::EntryType::Put, b"c".to_vec(), b"3".to_vec()),
+            ],
+            pairs(&path)
+        );
+        assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn log_append_or_roll_back() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("rollback.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 64).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.close().unwrap();
+        let before = fs::read(&path).unwrap();
+
+        let result = log::append_or_roll_back(&path, |writer| {
+            for i in 0..100u8 {
+                writer.put(&[i], &[i; 50])?;
+            }
+            Err(error::Error::NoPath)
+        });
+        match result {
+            Err(error::Error::NoPath) => {}
+            other => panic!("expected NoPath, got {:?}", other),
+        }
+        assert_eq!(before, fs::read(&path).unwrap());
+        log::Reader::open(&path).unwrap().verify().unwrap();
+
+        log::append_or_roll_back(&path, |writer| writer.put(b"b", b"2")).unwrap();
+        let reader = log::Reader::open(&path).unwrap();
+        assert_eq!(2, reader.entry_count());
+        reader.verify().unwrap();
+    }
+
+    #[test]
+    fn log_write_batch_drop() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("batch.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.close().unwrap();
+
+        let mut batch = log::WriteBatch::new(&path).unwrap();
+        batch.put(b"b", b"2").unwrap();
+        batch.delete(b"a").unwrap();
+        assert_eq!(2, fs::read_dir(dir.path()).unwrap().count());
+        drop(batch);
+
+        assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+        let reader = log::Reader::open(&path).unwrap();
+        assert_eq!(1, reader.entry_count());
+
+        let missing = dir.path().join("missing.spl");
+        let mut batch = log::WriteBatch::new(&missing).unwrap();
+        batch.put(b"b", b"2").unwrap();
+        drop(batch);
+        assert!(!missing.exists());
+        assert_eq!(1, fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn log_framed_values() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("framed.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 32).unwrap();
+        writer
+            .put_framed(b"record", &[b"first", b"", &[7; 100]])
+            .unwrap();
+        writer.put_framed(b"none", &[]).unwrap();
+        writer.put(b"plain", b"abc").unwrap();
+        writer.put(b"short", &[9, 0, 0, 0, 1]).unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let entries = reader
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(4 * 3 + 5 + 100, entries[0].value.len());
+        let parts = entries[0].framed_parts().unwrap();
+        assert_eq!(vec![&b"first"[..], &b""[..], &[7; 100][..]], parts);
+        assert!(entries[1].framed_parts().unwrap().is_empty());
+
+        match entries[2].framed_parts() {
+            Err(error::Error::InvalidFrame { position: 0, .. }) => {}
+            other => panic!("expected InvalidFrame, got {:?}", other),
+        }
+        let err = entries[3].framed_parts().unwrap_err();
+        assert_eq!(error::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn log_sample() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("sample.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 64).unwrap();
+        for i in 0..1000u32 {
+            writer
+                .put(&i.to_be_bytes(), format!("value{}", i).as_bytes())
+                .unwrap();
+        }
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let indices = |sample: Vec<log::Entry>| {
+            sample
+                .into_iter()
+                .map(|e| {
+                    let mut key = [0; 4];
+                    key.copy_from_slice(&e.key);
+                    let i = u32::from_be_bytes(key);
+                    assert_eq!(format!("value{}", i).as_bytes(), &e.value[..]);
+                    i
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = indices(reader.sample(10, 42).unwrap());
+        assert_eq!(10, first.len());
+        assert!(first.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(first, indices(reader.sample(10, 42).unwrap()));
+        assert_ne!(first, indices(reader.sample(10, 43).unwrap()));
+        // A uniform sample of 10 out of 1000 is very unlikely to come only from the start.
+        assert!(first.iter().any(|&i| i >= 100));
+
+        assert!(reader.sample(0, 42).unwrap().is_empty());
+        let all = indices(reader.sample(5000, 1).unwrap());
+        assert_eq!((0..1000).collect::<Vec<_>>(), all);
+    }
+
+    #[test]
+    fn log_sync_all() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("sync.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 64).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.sync_all().unwrap();
+
+        // Leak the writer instead of closing it, as a crash would.
+        mem::forget(writer);
+
+        let reader = log::Reader::open(&path).unwrap();
+        reader.verify().unwrap();
+        let pairs = reader
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let (key, value) = e.unwrap().into_pair();
+                (key.to_vec(), value.to_vec())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec())
+            ],
+            pairs
+        );
+
+        let mut writer = log::Writer::append(&path).unwrap();
+        writer.put(b"c", b"3").unwrap();
+        writer.sync_all().unwrap();
+        writer.close().unwrap();
+        assert_eq!(3, log::Reader::open(&path).unwrap().entry_count());
+    }
+
+    #[test]
+    fn error_from_returncode() {
+        use sparkey_sys::returncode;
+
+        let err = error::Error::from(returncode::FILE_NOT_FOUND);
+        assert_eq!(error::ErrorKind::NotFound, err.kind());
+        assert_eq!(
+            error::ErrorKind::Corrupt,
+            error::Error::from(returncode::LOG_HEADER_CORRUPT).kind()
+        );
+        match error::Error::from(returncode::SUCCESS) {
+            error::Error::Internal => {}
+            other => panic!("expected Internal, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "core-error")]
+    #[test]
+    fn error_code() {
+        use sparkey_sys::returncode;
+
+        let codes = [
+            returncode::FILE_NOT_FOUND,
+            returncode::FILE_SIZE_EXCEEDED,
+            returncode::UNEXPECTED_EOF,
+            returncode::WRONG_HASH_MAGIC_NUMBER,
+            returncode::LOG_ITERATOR_CLOSED,
+            returncode::HASH_SIZE_INVALID,
+        ];
+        for &returncode in &codes {
+            let code = error::Code::from_returncode(returncode);
+            let err = error::Error::from(returncode);
+            assert_eq!(Some(code), err.code());
+            assert_eq!(err.kind(), code.kind());
+        }
+
+        assert_eq!(
+            error::Code::Os(libc::ENOENT),
+            error::Code::from_returncode(returncode::FILE_NOT_FOUND)
+        );
+        assert_eq!(
+            "hash size invalid",
+            error::Code::HashSizeInvalid.to_string()
+        );
+        assert_eq!(None, error::Error::NoPath.code());
+    }
+
+    #[test]
+    fn hash_keys_values_live() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("live");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.put(b"a", b"3").unwrap();
+        writer.put(b"c", b"4").unwrap();
+        writer.delete(b"b").unwrap();
+        writer.put(b"c", b"5").unwrap();
+        let map = writer.close().unwrap();
+        let hash = map.hash_reader();
+
+        let to_vecs =
+            |items: Vec<bytes::BytesMut>| items.into_iter().map(|b| b.to_vec()).collect::<Vec<_>>();
+
+        let raw_keys = hash.log_reader().collect_keys().unwrap();
+        assert_eq!(
+            vec![&b"a"[..], b"b", b"a", b"c", b"b", b"c"],
+            raw_keys.iter().map(|k| &k[..]).collect::<Vec<_>>()
+        );
+        let live_keys = hash
+            .keys()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![b"a".to_vec(), b"c".to_vec()], to_vecs(live_keys));
+
+        let raw_values = hash.log_reader().collect_values().unwrap();
+        assert_eq!(6, raw_values.len());
+        let live_values = hash
+            .values()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(vec![b"3".to_vec(), b"5".to_vec()], to_vecs(live_values));
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    #[test]
+    fn to_btreemap() {
+        use std::convert::TryFrom;
+
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("btreemap");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::None, 0).unwrap();
+        writer.put(b"d", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.put(b"a", b"3").unwrap();
+        writer.delete(b"b").unwrap();
+        writer.put(b"c", b"4").unwrap();
+        writer.put(b"d", b"5").unwrap();
+        let map = writer.close().unwrap();
+
+        let btreemap = map.hash_reader().to_btreemap().unwrap();
+        let pairs = btreemap
+            .iter()
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (b"a".to_vec(), b"3".to_vec()),
+                (b"c".to_vec(), b"4".to_vec()),
+                (b"d".to_vec(), b"5".to_vec()),
+            ],
+            pairs
+        );
+        assert_eq!(
+            btreemap,
+            collections::BTreeMap::try_from(map.hash_reader()).unwrap()
+        );
+    }
+
+    #[test]
+    fn map_diff() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base_a = dir.path().join("a");
+        let base_b = dir.path().join("b");
+
+        let mut writer = map::Map::create(&base_a, log::CompressionType::None, 0).unwrap();
+        writer.put(b"same", b"1").unwrap();
+        writer.put(b"changed", b"old").unwrap();
+        writer.put(b"removed", b"gone").unwrap();
+        writer.put(b"readded", b"x").unwrap();
+        writer.delete(b"readded").unwrap();
+        writer.close().unwrap();
+
+        let mut writer = map::Map::create(&base_b, log::CompressionType::Snappy, 64).unwrap();
+        writer.put(b"added", b"new").unwrap();
+        writer.put(b"changed", b"new").unwrap();
+        writer.put(b"same", b"0").unwrap();
+        writer.put(b"same", b"1").unwrap();
+        writer.put(b"readded", b"y").unwrap();
+        writer.close().unwrap();
+
+        let bytes = |b: &[u8]| bytes::BytesMut::from(b);
+        assert_eq!(
+            vec![
+                map::Diff::Added(bytes(b"added"), bytes(b"new")),
+                map::Diff::Changed(bytes(b"changed"), bytes(b"old"), bytes(b"new")),
+                map::Diff::Added(bytes(b"readded"), bytes(b"y")),
+                map::Diff::Removed(bytes(b"removed"), bytes(b"gone")),
+            ],
+            map::diff(&base_a, &base_b).unwrap()
+        );
+        assert_eq!(
+            &b"changed"[..],
+            map::diff(&base_a, &base_b).unwrap()[1].key()
+        );
+
+        assert!(map::diff(&base_a, &base_a).unwrap().is_empty());
+        assert_eq!(
+            vec![
+                map::Diff::Removed(bytes(b"added"), bytes(b"new")),
+                map::Diff::Changed(bytes(b"changed"), bytes(b"new"), bytes(b"old")),
+                map::Diff::Removed(bytes(b"readded"), bytes(b"y")),
+                map::Diff::Added(bytes(b"removed"), bytes(b"gone")),
+            ],
+            map::diff(&base_b, &base_a).unwrap()
+        );
+    }
+
+    #[test]
+    fn log_lazy_values() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("lazy.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 128).unwrap();
+        for i in 0..20u8 {
+            writer.put(&[i], &[i; 50]).unwrap();
+        }
+        writer.delete(&[3]).unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let pool = sync::Arc::new(CountingPool::default());
+        let mut entries = reader.entries().unwrap();
+        entries.set_pool(pool.clone());
+
+        let mut read = Vec::new();
+        let mut count = 0;
+        while let Some(mut entry) = entries.next_lazy().unwrap() {
+            count += 1;
+            assert_eq!(1, entry.key.len());
+            let i = entry.key[0];
+            if entry.entry_type == log::EntryType::Delete {
+                assert!(entry.value.is_empty());
+                continue;
+            }
+            assert_eq!(50, entry.value.len());
+            if i % 5 == 0 {
+                assert_eq!(&[i; 50][..], entry.value.as_bytes().unwrap());
+                // Accessing the value again doesn't read it again.
+                assert_eq!(&[i; 50][..], entry.value.as_bytes().unwrap());
+                read.push(entry.value.into_bytes().unwrap().to_vec());
+            } else if i == 7 {
+                assert_eq!(&[7; 50][..], &entry.value.into_bytes().unwrap()[..]);
+            }
+        }
+        assert_eq!(21, count);
+        assert_eq!(
+            vec![vec![0; 50], vec![5; 50], vec![10; 50], vec![15; 50]],
+            read
+        );
+        // One buffer for each of the 21 keys and the 5 values that were accessed.
+        assert_eq!(26, pool.acquired());
+    }
+
+    #[test]
+    fn log_append_from_reader() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src_path = dir.path().join("src.spl");
+        let dst_path = dir.path().join("dst.spl");
+
+        let mut writer = log::Writer::create(&src_path, log::CompressionType::Snappy, 32).unwrap();
+        for i in 0..10u8 {
+            writer.put(&[i], &[i; 30]).unwrap();
+        }
+        writer.delete(&[2]).unwrap();
+        writer.put(&[2], b"again").unwrap();
+        writer.close().unwrap();
+
+        let mut writer = log::Writer::create(&dst_path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"existing", b"entry").unwrap();
+        let src = log::Reader::open(&src_path).unwrap();
+        assert_eq!(12, writer.append_from_reader(&src).unwrap());
+        writer.close().unwrap();
+
+        let dst = log::Reader::open(&dst_path).unwrap();
+        assert_eq!(log::CompressionType::None, dst.compression_type());
+        let dst_entries = dst
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        let src_entries = src
+            .entries()
+            .unwrap()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(13, dst_entries.len());
+        assert_eq!(&b"existing"[..], &dst_entries[0].key[..]);
+        assert_eq!(&src_entries[..], &dst_entries[1..]);
+        assert_eq!(log::EntryType::Delete, dst_entries[11].entry_type);
+    }
+
+    #[test]
+    fn log_entry_cmp_key() {
+        let entry = |entry_type, key: &[u8], value: &[u8]| log::Entry {
+            entry_type,
+            key: bytes::BytesMut::from(key),
+            value: bytes::BytesMut::from(value),
+        };
+
+        let mut entries = [
+            entry(log::EntryType::Put, b"b", b"1"),
+            entry(log::EntryType::Delete, b"ab", b""),
+            entry(log::EntryType::Put, b"", b"2"),
+            entry(log::EntryType::Put, b"a", b"3"),
+        ];
+        entries.sort_by(log::entry_key_cmp);
+        assert_eq!(
+            vec![&b""[..], b"a", b"ab", b"b"],
+            entries.iter().map(|e| &e.key[..]).collect::<Vec<_>>()
+        );
+
+        let put = entry(log::EntryType::Put, b"a", b"1");
+        let delete = entry(log::EntryType::Delete, b"a", b"");
+        assert_eq!(cmp::Ordering::Equal, put.cmp_key(&delete));
+        assert_eq!(cmp::Ordering::Less, put.cmp_key(&entries[3]));
+        assert_eq!(cmp::Ordering::Greater, entries[3].cmp_key(&put));
+    }
+
+    #[test]
+    fn log_scan_metrics() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("metrics.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::Snappy, 64).unwrap();
+        for i in 0..10u8 {
+            writer.put(&[i; 3], &[i; 20]).unwrap();
+        }
+        writer.delete(&[0; 3]).unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let mut entries = reader.entries().unwrap().with_metrics();
+        assert_eq!(log::ScanMetrics::default(), entries.metrics());
+
+        let start = std::time::Instant::now();
+        entries.by_ref().take(4).for_each(|e| drop(e.unwrap()));
+        assert_eq!(4, entries.metrics().entries);
+        assert_eq!(4 * 23, entries.metrics().bytes_read);
+
+        entries.by_ref().for_each(|e| drop(e.unwrap()));
+        let wall = start.elapsed();
+        let metrics = entries.metrics();
+        assert_eq!(11, metrics.entries);
+        assert_eq!(10 * 23 + 3, metrics.bytes_read);
+        assert!(metrics.elapsed <= wall);
+
+        assert!(entries.next().is_none());
+        assert_eq!(metrics.entries, entries.metrics().entries);
+        assert!(entries.into_inner().next().is_none());
+    }
+
+    #[test]
+    fn hash_validate_against_log() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log_path = dir.path().join("stale.spl");
+        let hash_path = dir.path().join("stale.spi");
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.close().unwrap();
+        hash::write(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+
+        let reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+        reader.validate_against_log().unwrap();
+
+        let mut writer = log::Writer::append(&log_path).unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.close().unwrap();
+
+        // Both the reader opened before the append and one opened after it see the stale index.
+        for reader in &[reader, hash::Reader::open(&hash_path, &log_path).unwrap()] {
+            let err = reader.validate_against_log().unwrap_err();
+            match err {
+                error::Error::StaleIndex {
+                    index_data_end,
+                    log_data_end,
+                } => assert!(index_data_end < log_data_end),
+                ref other => panic!("expected StaleIndex, got {:?}", other),
+            }
+            assert_eq!(error::ErrorKind::Corrupt, err.kind());
+            assert!(err.to_string().starts_with("stale index"));
+        }
+
+        hash::write(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+        let reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+        reader.validate_against_log().unwrap();
+
+        // A reader keeps the index it mapped after another one replaces it.
+        let mut writer = log::Writer::append(&log_path).unwrap();
+        writer.put(b"c", b"3").unwrap();
+        writer.close().unwrap();
+        hash::write_atomic(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+        assert!(!reader.contains_key(b"c").unwrap());
+        match reader.validate_against_log() {
+            Err(error::Error::StaleIndex { .. }) => {}
+            other => panic!("expected StaleIndex, got {:?}", other),
+        }
+        let reader = hash::Reader::open(&hash_path, &log_path).unwrap();
+        reader.validate_against_log().unwrap();
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::None, 0).unwrap();
+        writer.put(b"a", b"1").unwrap();
+        writer.put(b"b", b"2").unwrap();
+        writer.close().unwrap();
+        match reader.validate_against_log() {
+            Err(error::Error::FileIdentifierMismatch) => {}
+            other => panic!("expected FileIdentifierMismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn log_nth_entry() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log_path = dir.path().join("pages.spl");
+        let hash_path = dir.path().join("pages.spi");
+
+        let mut writer = log::Writer::create(&log_path, log::CompressionType::Snappy, 64).unwrap();
+        for i in 0..23u8 {
+            writer.put(&[i], &[i; 10]).unwrap();
+        }
+        writer.close().unwrap();
+        hash::write(&hash_path, &log_path, hash::HashSize::Auto).unwrap();
+
+        let reader = log::Reader::open(&log_path).unwrap();
+        let page_size = 5;
+        let mut pages = Vec::new();
+        for page in 0.. {
+            let mut entries = reader.entries().unwrap();
+            let first = match entries.nth_entry(page * page_size).unwrap() {
+                Some(entry) => entry,
+                None => break,
+            };
+            let mut keys = vec![first.key[0]];
+            for entry in entries.take(page_size as usize - 1) {
+                keys.push(entry.unwrap().key[0]);
+            }
+            pages.push(keys);
+        }
+        assert_eq!(
+            vec![
+                vec![0, 1, 2, 3, 4],
+                vec![5, 6, 7, 8, 9],
+                vec![10, 11, 12, 13, 14],
+                vec![15, 16, 17, 18, 19],
+                vec![20, 21, 22],
+            ],
+            pages
+        );
+
+        let mut entries = reader.entries().unwrap();
+        assert_eq!(&[0][..], &entries.nth_entry(0).unwrap().unwrap().key[..]);
+        assert_eq!(&[3][..], &entries.nth_entry(2).unwrap().unwrap().key[..]);
+        assert!(entries.nth_entry(100).unwrap().is_none());
+        assert!(entries.nth_entry(0).unwrap().is_none());
+
+        let hash = hash::Reader::open(&hash_path, &log_path).unwrap();
+        let mut entries = hash.entries().unwrap();
+        assert!(entries.seek(&[10]).unwrap());
+        assert_eq!(&[10][..], &entries.nth_entry(0).unwrap().unwrap().key[..]);
+        assert_eq!(&[12][..], &entries.nth_entry(1).unwrap().unwrap().key[..]);
+    }
+
+    #[test]
+    fn hash_builder() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let log_path = dir.path().join("built.spl");
+        let hash_path = dir.path().join("built.spi");
+
+        let mut builder = hash::Builder::create(
+            &hash_path,
+            &log_path,
+            log::WriterConfig::default().snappy(64),
+        )
+        .unwrap();
+        builder.set_hash_size(hash::HashSize::Bits64);
+        builder.put(b"a", b"1").unwrap();
+        builder.put(b"b", b"2").unwrap();
+        builder.delete(b"a").unwrap();
+        builder.log_writer().put(b"c", b"3").unwrap();
+        assert!(!hash_path.exists());
+
+        let reader = builder.finish().unwrap();
+        assert!(hash_path.exists());
+        assert_eq!(2, reader.len());
+        assert_eq!(None, reader.get(b"a").unwrap());
+        assert_eq!(Some(&b"2"[..]), reader.get(b"b").unwrap().as_deref());
+        assert_eq!(Some(&b"3"[..]), reader.get(b"c").unwrap().as_deref());
+        assert_eq!(log::CompressionType::Snappy, reader.compression_type());
+        assert_eq!(hash::HashSize::Bits64, reader.header().unwrap().hash_size);
+        reader.validate_against_log().unwrap();
+    }
+
+    #[test]
+    fn hash_key_status() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let base = dir.path().join("status");
+
+        let mut writer = map::Map::create(&base, log::CompressionType::Snappy, 64).unwrap();
+        writer.put(b"live", b"1").unwrap();
+        writer.put(b"deleted", b"2").unwrap();
+        writer.delete(b"deleted").unwrap();
+        writer.delete(b"never-put").unwrap();
+        writer.put(b"revived", b"3").unwrap();
+        writer.delete(b"revived").unwrap();
+        writer.put(b"revived", b"4").unwrap();
+        let map = writer.close().unwrap();
+        let reader = map.hash_reader();
+
+        assert_eq!(
+            hash::KeyStatus::Present,
+            reader.key_status(b"live").unwrap()
+        );
+        assert_eq!(
+            hash::KeyStatus::Present,
+            reader.key_status(b"revived").unwrap()
+        );
+        assert_eq!(
+            hash::KeyStatus::Deleted,
+            reader.key_status(b"deleted").unwrap()
+        );
+        assert_eq!(
+            hash::KeyStatus::Deleted,
+            reader.key_status(b"never-put").unwrap()
+        );
+        assert_eq!(
+            hash::KeyStatus::Absent,
+            reader.key_status(b"absent").unwrap()
+        );
+        assert!(!reader.contains_key(b"deleted").unwrap());
+        assert!(!reader.contains_key(b"absent").unwrap());
+
+        // A put the index doesn't cover is not reported as absent.
+        let mut writer = log::Writer::append(base.with_extension("spl")).unwrap();
+        writer.put(b"unindexed", b"5").unwrap();
+        writer.close().unwrap();
+        let stale =
+            hash::Reader::open(base.with_extension("spi"), base.with_extension("spl")).unwrap();
+        match stale.key_status(b"unindexed") {
+            Err(error::Error::StaleIndex { .. }) => {}
+            other => panic!("expected StaleIndex, got {:?}", other),
+        }
+        assert_eq!(
+            hash::KeyStatus::Absent,
+            stale.key_status(b"absent").unwrap()
+        );
+    }
+
+    #[test]
+    fn log_as_strings() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("strings.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        writer.put("clé".as_bytes(), "värde".as_bytes()).unwrap();
+        writer.delete(b"gone").unwrap();
+        writer.put(b"bad", b"caf\xe9").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let mut strings = reader.entries().unwrap().as_strings();
+        assert_eq!(
+            ("clé".to_owned(), "värde".to_owned()),
+            strings.next().unwrap().unwrap()
+        );
+        assert_eq!(
+            ("gone".to_owned(), String::new()),
+            strings.next().unwrap().unwrap()
+        );
+        match strings.next().unwrap() {
+            Err(error::Error::InvalidUtf8 {
+                field: "value",
+                lossy,
+            }) => assert_eq!("caf\u{fffd}", lossy),
+            other => panic!("expected InvalidUtf8, got {:?}", other),
+        }
+        assert!(strings.next().is_none());
+
+        let lossy = reader
+            .entries()
+            .unwrap()
+            .as_strings_lossy()
+            .collect::<error::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                ("clé".to_owned(), "värde".to_owned()),
+                ("gone".to_owned(), String::new()),
+                ("bad".to_owned(), "caf\u{fffd}".to_owned()),
+            ],
+            lossy
+        );
+    }
+
+    #[test]
+    fn log_append_from_reader_throughput() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let src_path = dir.path().join("src.spl");
+        let naive_path = dir.path().join("naive.spl");
+        let fast_path = dir.path().join("fast.spl");
+
+        let mut writer =
+            log::Writer::create(&src_path, log::CompressionType::Snappy, 4096).unwrap();
+        for i in 0..5000u32 {
+            writer
+                .put(format!("key{:05}", i).as_bytes(), &[i as u8; 100])
+                .unwrap();
+        }
+        writer.close().unwrap();
+        let src = log::Reader::open(&src_path).unwrap();
+
+        let copy = |path: &path::Path, fast: bool| {
+            let mut writer = log::Writer::create(path, log::CompressionType::None, 0).unwrap();
+            let start = std::time::Instant::now();
+            let before = ALLOCATIONS.with(|n| n.get());
+            if fast {
+                assert_eq!(5000, writer.append_from_reader(&src).unwrap());
+            } else {
+                for entry in src.entries().unwrap() {
+                    writer.put_entry(&entry.unwrap()).unwrap();
+                }
+            }
+            let allocations = ALLOCATIONS.with(|n| n.get()) - before;
+            let elapsed = start.elapsed();
+            writer.close().unwrap();
+            (allocations, elapsed)
+        };
+
+        let (naive_allocations, naive_elapsed) = copy(&naive_path, false);
+        let (fast_allocations, fast_elapsed) = copy(&fast_path, true);
+        assert!(naive_elapsed.as_nanos() > 0 && fast_elapsed.as_nanos() > 0);
+
+        // The naive copy allocates a buffer for every value.
+        assert!(
+            naive_allocations >= 5000,
+            "{} allocations",
+            naive_allocations
+        );
+        assert!(fast_allocations <= 4, "{} allocations", fast_allocations);
+        assert_eq!(
+            fs::read(&naive_path).unwrap().len(),
+            fs::read(&fast_path).unwrap().len()
+        );
+        let copied = log::Reader::open(&fast_path).unwrap();
+        let expected: Vec<_> = src.entries().unwrap().map(Result::unwrap).collect();
+        let actual: Vec<_> = copied.entries().unwrap().map(Result::unwrap).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn log_writer_max_lens() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let path = dir.path().join("test.spl");
+
+        let mut writer = log::Writer::create(&path, log::CompressionType::None, 0).unwrap();
+        assert_eq!(u64::MAX, writer.max_value_len());
+        writer.set_max_key_len(4);
+        writer.set_max_value_len(8);
+
+        writer.put(b"abcd", &[1; 8]).unwrap();
+        match writer.put(b"abcd", &[1; 9]) {
+            Err(error::Error::TooLarge { what, value, max }) => {
+                assert_eq!(("value length", 9, 8), (what, value, max))
+            }
+            r => panic!("{:?}", r),
+        }
+        match writer.put(b"abcde", b"") {
+            Err(error::Error::TooLarge { what, value, max }) => {
+                assert_eq!(("key length", 5, 4), (what, value, max))
+            }
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(
+            error::ErrorKind::TooLarge,
+            writer.delete(b"abcde").unwrap_err().kind()
+        );
+        assert_eq!(
+            error::ErrorKind::TooLarge,
+            writer.put_reader(b"a", 9, &[1; 9][..]).unwrap_err().kind()
+        );
+        writer.put_reader(b"a", 8, &[2; 8][..]).unwrap();
+        writer.delete(b"abcd").unwrap();
+        writer.close().unwrap();
+
+        let reader = log::Reader::open(&path).unwrap();
+        let keys: Vec<_> = reader
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().key.to_vec())
+            .collect();
+        assert_eq!(
+            vec![b"abcd".to_vec(), b"a".to_vec(), b"abcd".to_vec()],
+            keys
+        );
+    }
+
+    #[test]
+    fn map_dotted_bases() {
+        let dir = tempdir::TempDir::new("sparkey-rs").unwrap();
+        let first = dir.path().join("daily.2024-01-01");
+        let second = dir.path().join("daily.2024-01-02");
+
+        let mut writer = map::Map::create(&first, log::CompressionType::None, 0).unwrap();
+        writer.put(b"first", b"1").unwrap();
+        writer.close().unwrap();
+        let mut writer = map::Map::create(&second, log::CompressionType::None, 0).unwrap();
+        writer.put(b"second", b"2").unwrap();
+        writer.close().unwrap();
+
+        assert!(dir.path().join("daily.2024-01-01.spl").exists());
+        assert!(dir.path().join("daily.2024-01-01.spi").exists());
+        assert!(!dir.path().join("daily.spl").exists());
+
+        let map = map::Map::open(&first).unwrap();
+        assert_eq!(Some(&b"1"[..]), map.get(b"first").unwrap().as_deref());
+        assert!(map.get(b"second").unwrap().is_none());
+        let map = map::Map::open(&second).unwrap();
+        assert_eq!(Some(&b"2"[..]), map.get(b"second").unwrap().as_deref());
+    }
+
     #[test]
     fn read_small() {
         use std::io::BufRead;
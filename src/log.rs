@@ -1,43 +1,306 @@
+use std::cmp;
+use std::collections;
+use std::env;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::iter;
+use std::marker;
+use std::mem;
 use std::os;
 use std::path;
+use std::process;
 use std::ptr;
+use std::slice;
+use std::str;
+use std::sync;
+use std::sync::atomic;
+use std::time;
 
+use failure::Fail;
 use sparkey_sys::*;
 
 use crate::error;
+use crate::hash;
 use crate::util;
 
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+// Size of the (version 1.0) log header; the first block starts right after it.
+const LOG_HEADER_SIZE: u64 = 84;
+
+// Smallest block size accepted for compressed logs.
+const MIN_COMPRESSION_BLOCK_SIZE: u32 = 10;
+
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CompressionType {
+    #[default]
     None,
     Snappy,
 }
 
-#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EntryType {
     Put,
     Delete,
 }
 
+/// The metadata stored in a log file's header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LogHeader {
+    pub major_version: u32,
+    pub minor_version: u32,
+    /// Random identifier shared by the log and the hash index built from it.
+    pub file_identifier: u32,
+    pub num_puts: u64,
+    pub num_deletes: u64,
+    /// Offset of the end of the last block.
+    pub data_end: u64,
+    pub max_key_len: u64,
+    pub max_value_len: u64,
+    /// Total size of all puts, in bytes.
+    pub put_size: u64,
+    /// Total size of all deletes, in bytes.
+    pub delete_size: u64,
+    pub compression_type: CompressionType,
+    pub compression_block_size: u32,
+    pub max_entries_per_block: u32,
+}
+
+/// Totals gathered by scanning a log with `Reader::stats`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LogStats {
+    pub num_puts: u64,
+    pub num_deletes: u64,
+    /// Keys whose last entry is a put.
+    pub num_live_keys: u64,
+    /// Total key length of all entries, puts and deletes alike.
+    pub total_key_len: u64,
+    /// Total value length of all puts.
+    pub total_value_len: u64,
+    pub min_value_len: Option<u64>,
+    pub max_value_len: Option<u64>,
+}
+
+/// Counts gathered by `Entries::with_metrics` over a scan.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ScanMetrics {
+    /// Entries yielded, puts and deletes alike.
+    pub entries: u64,
+    /// Total length of the keys and values yielded.
+    pub bytes_read: u64,
+    /// Time spent advancing the iterator and reading entries.
+    pub elapsed: time::Duration,
+}
+
+/// The parameters for creating a log with `Writer::create_with`.
+///
+/// The default is an uncompressed log.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WriterConfig {
+    pub compression: CompressionType,
+    /// Ignored for uncompressed logs.
+    pub block_size: u32,
+}
+
+/// How `hash::Reader::export_ndjson` writes keys and values; `Writer::import_ndjson` reads both.
+///
+/// Each line is a JSON object with a `type` (`"put"` or `"delete"`), and the key and value either
+/// as base64 in `key` and `value` or as text in `key_utf8` and `value_utf8`.  Deletes have no
+/// value, and may be marked with `"delete": true` instead of a `type`.
+#[cfg(feature = "ndjson")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NdjsonEncoding {
+    /// Base64 in the `key` and `value` fields.
+    Base64,
+    /// Text in the `key_utf8` and `value_utf8` fields when it is valid UTF-8, base64 otherwise.
+    Utf8IfValid,
+}
+
+/// The state of a log iterator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IterState {
+    /// Created or reset, but not advanced yet.
+    New,
+    /// Positioned at an entry.
+    Active,
+    /// Past the last entry.
+    Closed,
+    /// At an undefined position, after a failed lookup or read.
+    Invalid,
+}
+
+/// The position of an entry in a log, ordered like the entries themselves.
+///
+/// Entries are addressed by the block they start in and their index among the entries starting in
+/// that block.  Uncompressed logs have one entry per block, so `block_position` is then the byte
+/// offset of the entry and `entry_index` is always 0.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Offset {
+    pub block_position: u64,
+    pub entry_index: u32,
+}
+
 #[derive(Debug)]
-pub struct Reader(*mut logreader, bool);
+pub struct Reader(*mut logreader, bool, Option<path::PathBuf>);
 
 #[derive(Debug)]
-pub struct Writer(*mut logwriter);
+pub struct Writer {
+    raw: *mut logwriter,
+    flush_interval: Option<u64>,
+    // The number of puts and deletes written since the last flush.
+    unflushed: u64,
+    flushes: u64,
+    max_key_len: u64,
+    max_value_len: u64,
+}
+
+/// Queues puts and deletes in memory and writes them to a `Writer` in batches, flushing once per
+/// batch.
+///
+/// Queued entries are written when the queue reaches its capacity, on `flush`, and when the
+/// `BufWriter` is dropped; errors while dropping are logged, so prefer `close`.
+///
+/// This saves flushes, each of which rewrites the log header, compared to a `Writer` flushing
+/// every few entries with `set_flush_interval`, while readers still see each batch once it is
+/// written.  It does not save calls into the C library: each entry is copied into the queue and
+/// then written with its own call, like `Writer::put` does.
+#[derive(Debug)]
+pub struct BufWriter {
+    writer: Writer,
+    // The wrapped writer's flush interval, suspended while the `BufWriter` does the flushing.
+    flush_interval: Option<u64>,
+    queue: Vec<Entry>,
+    capacity: usize,
+    batches: u64,
+}
 
+/// Puts and deletes staged in a temporary log next to a target log, and added to it all at once
+/// by `commit`.
+///
+/// If the target log exists, `commit` appends the staged entries and flushes once, so readers
+/// opened afterwards see either none or all of them: the log header, which bounds what readers
+/// see, is only rewritten by that flush, and a failed append is rolled back.  Otherwise the
+/// staged log is linked into place.  Dropping the batch without committing discards the staged
+/// entries.
 #[derive(Debug)]
+pub struct WriteBatch {
+    writer: Option<Writer>,
+    path: path::PathBuf,
+    target: path::PathBuf,
+}
+
+/// A log in a temporary file, built from key/value pairs and removed when dropped.
+///
+/// The `FromIterator` implementation panics on IO errors; use `try_from_iter` to handle them.
+#[derive(Debug)]
+pub struct Builder(path::PathBuf);
+
+/// Entries compare and hash by their type, key and value.
+#[derive(Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entry {
     pub entry_type: EntryType,
+    #[cfg_attr(feature = "serde", serde(with = "util::serde_bytes"))]
     pub key: bytes::BytesMut,
+    #[cfg_attr(feature = "serde", serde(with = "util::serde_bytes"))]
     pub value: bytes::BytesMut,
 }
 
-pub struct Entries<'a>(*mut logiter, &'a Reader, Option<*mut hashreader>);
+/// An `Entry` with reference-counted key and value, which are cheap to clone and share.
+///
+/// Keys and values of up to 31 bytes are stored inline by `bytes`, so only longer ones share an
+/// allocation when cloned.
+#[derive(Clone, Debug)]
+pub struct SharedEntry {
+    pub entry_type: EntryType,
+    pub key: bytes::Bytes,
+    pub value: bytes::Bytes,
+}
+
+/// A source of buffers for the keys and values read by iterators, set with `set_pool`.
+///
+/// Iterators call `acquire` for every key and value they read and replace the contents of the
+/// returned buffer, so a pool that hands out buffers returned by the caller lets their
+/// allocations be reused.
+pub trait BytesPool {
+    /// Returns a buffer that will be filled with `len` bytes.  It is cleared before use and grown
+    /// if its capacity is too small.
+    fn acquire(&self, len: usize) -> bytes::BytesMut;
+}
+
+pub struct Entries<'a>(Iter, marker::PhantomData<&'a Reader>);
+
+/// The live entries of a log, iterated through a `hash::Reader` the iterator owns.
+///
+/// Unlike `Entries` this doesn't borrow a reader, so it can be returned from functions.
+pub struct OwnedEntries(Entries<'static>, hash::Reader);
+
+pub struct SharedEntries<'a>(Entries<'a>);
+
+/// The keys and values of the puts of an `Entries` iterator.
+pub struct Puts<'a>(Entries<'a>);
+
+/// The keys of the deletes of an `Entries` iterator.
+pub struct Deletes<'a>(Entries<'a>);
+
+/// The deletes of a log, each with the value of the put it deleted.
+pub struct DeletionsWithPrior<'a> {
+    entries: Entries<'a>,
+    live: collections::HashMap<Vec<u8>, bytes::BytesMut>,
+}
+
+/// The keys and values of an `Entries` iterator as strings, failing on bytes that aren't UTF-8.
+pub struct Strings<'a>(Entries<'a>);
+
+/// The keys and values of an `Entries` iterator as strings, with bytes that aren't UTF-8
+/// replaced by U+FFFD.
+pub struct LossyStrings<'a>(Entries<'a>);
+
+/// An `Entries` iterator that keeps `ScanMetrics` for the entries it yields.
+pub struct WithMetrics<'a>(Entries<'a>, ScanMetrics);
+
+/// The entries of an `Entries` iterator whose keys match a predicate.
+pub struct FilterKeys<'a, F>(Entries<'a>, F);
+
+pub struct Keys<'a>(Iter, marker::PhantomData<&'a Reader>);
 
-pub struct Keys<'a>(*mut logiter, &'a Reader, Option<*mut hashreader>);
+pub struct Values<'a>(Iter, marker::PhantomData<&'a Reader>);
 
-pub struct Values<'a>(*mut logiter, &'a Reader, Option<*mut hashreader>);
+/// An entry from `Entries::next_lazy`, whose value is only read when asked for.
+pub struct LazyEntry<'a> {
+    pub entry_type: EntryType,
+    pub key: bytes::BytesMut,
+    pub value: LazyValue<'a>,
+}
+
+/// The value of the current entry of an iterator, read on first access.
+pub struct LazyValue<'a>(&'a mut Iter, Option<bytes::BytesMut>);
+
+/// The value of the current entry of an iterator, read one chunk at a time without copying.
+pub struct ValueChunks<'a>(&'a mut Iter);
+
+/// The value of the current entry of an iterator, exposed through `io::Read`.
+pub struct ValueReader<'a>(&'a mut Iter, u64);
+
+struct Iter {
+    raw: *mut logiter,
+    log: *mut logreader,
+    hash: Option<*mut hashreader>,
+    pending: bool,
+    // Number of live entries left, known only for hash-backed iterators that have not been
+    // repositioned.
+    remaining: Option<u64>,
+    // Position of the current entry, captured before reading it moves the iterator on.
+    offset: Option<Offset>,
+    pool: Option<sync::Arc<dyn BytesPool + Send + Sync>>,
+}
+
+/// Compares two entries by key, for use with `sort_by` and merge-style algorithms.
+pub fn entry_key_cmp(a: &Entry, b: &Entry) -> cmp::Ordering {
+    a.cmp_key(b)
+}
 
 impl CompressionType {
     pub fn from_raw(raw: compression_type) -> Self {
@@ -53,6 +316,54 @@ impl CompressionType {
             CompressionType::Snappy => compression_type::COMPRESSION_SNAPPY,
         }
     }
+
+    /// A sensible compression block size to use with this compression type.
+    pub fn block_size_hint(self) -> u32 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Snappy => 4096,
+        }
+    }
+
+    fn check_block_size(self, block_size: u32) -> error::Result<u32> {
+        match self {
+            CompressionType::None => Ok(0),
+            CompressionType::Snappy if block_size < MIN_COMPRESSION_BLOCK_SIZE => {
+                Err(error::Error::CompressionBlockSizeTooSmall {
+                    compression_type: self,
+                    block_size,
+                    minimum: MIN_COMPRESSION_BLOCK_SIZE,
+                })
+            }
+            CompressionType::Snappy => Ok(block_size),
+        }
+    }
+}
+
+impl WriterConfig {
+    pub fn uncompressed(self) -> Self {
+        WriterConfig {
+            compression: CompressionType::None,
+            block_size: 0,
+        }
+    }
+
+    pub fn snappy(self, block_size: u32) -> Self {
+        WriterConfig {
+            compression: CompressionType::Snappy,
+            block_size,
+        }
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "block {}, entry {}",
+            self.block_position, self.entry_index
+        )
+    }
 }
 
 impl EntryType {
@@ -71,8 +382,38 @@ impl EntryType {
     }
 }
 
+impl IterState {
+    pub fn from_raw(raw: iter_state) -> Self {
+        match raw {
+            iter_state::ITER_NEW => IterState::New,
+            iter_state::ITER_ACTIVE => IterState::Active,
+            iter_state::ITER_CLOSED => IterState::Closed,
+            iter_state::ITER_INVALID => IterState::Invalid,
+        }
+    }
+
+    pub fn as_raw(self) -> iter_state {
+        match self {
+            IterState::New => iter_state::ITER_NEW,
+            IterState::Active => iter_state::ITER_ACTIVE,
+            IterState::Closed => iter_state::ITER_CLOSED,
+            IterState::Invalid => iter_state::ITER_INVALID,
+        }
+    }
+}
+
 impl Writer {
-    #[allow(clippy::cast_possible_wrap)]
+    fn new(raw: *mut logwriter) -> Self {
+        Writer {
+            raw,
+            flush_interval: None,
+            unflushed: 0,
+            flushes: 0,
+            max_key_len: u64::MAX,
+            max_value_len: u64::MAX,
+        }
+    }
+
     pub fn create<P>(
         path: P,
         compression_type: CompressionType,
@@ -83,17 +424,28 @@ impl Writer {
     {
         let mut raw = ptr::null_mut();
         let path = util::path_to_cstring(path)?;
+        let compression_block_size = util::to_c_int(
+            "compression block size",
+            compression_type.check_block_size(compression_block_size)?,
+        )?;
 
         util::handle(unsafe {
             logwriter_create(
                 &mut raw,
                 path.as_ptr(),
                 compression_type.as_raw(),
-                compression_block_size as os::raw::c_int,
+                compression_block_size,
             )
         })?;
 
-        Ok(Self(raw))
+        Ok(Self::new(raw))
+    }
+
+    pub fn create_with<P>(path: P, config: WriterConfig) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        Self::create(path, config.compression, config.block_size)
     }
 
     pub fn append<P>(path: P) -> error::Result<Self>
@@ -105,272 +457,1926 @@ impl Writer {
 
         util::handle(unsafe { logwriter_append(&mut raw, path.as_ptr()) })?;
 
-        Ok(Self(raw))
+        Ok(Self::new(raw))
+    }
+
+    /// Opens an existing log for appending, failing with `CompressionTypeMismatch` unless it uses
+    /// the `expected` compression type.
+    pub fn append_checked<P>(path: P, expected: CompressionType) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        let actual = Reader::open(&path)?.compression_type();
+
+        if actual != expected {
+            return Err(error::Error::CompressionTypeMismatch { expected, actual });
+        }
+
+        Self::append(path)
     }
 
+    /// # Safety
+    ///
+    /// `raw` must be a valid, open log writer; it will be closed when the `Writer` is dropped.
     pub unsafe fn from_raw(raw: *mut logwriter) -> Self {
-        Self(raw)
+        Self::new(raw)
+    }
+
+    /// Like `from_raw`, but fails with `NullPointer` instead of wrapping a null pointer.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_raw`, if `raw` is not null.
+    pub unsafe fn from_raw_checked(raw: *mut logwriter) -> error::Result<Self> {
+        if raw.is_null() {
+            Err(error::Error::NullPointer)
+        } else {
+            Ok(Self::from_raw(raw))
+        }
     }
 
     pub fn as_raw(&self) -> *mut logwriter {
-        self.0
+        self.raw
     }
 
+    /// Appends a put of `key` with `value`.
+    ///
+    /// Keys and values may be empty: entries store their lengths, so an empty key is a key like
+    /// any other (it can be looked up, overwritten and deleted) and reads back as an empty buffer.
     pub fn put(&mut self, key: &[u8], value: &[u8]) -> error::Result<()> {
+        self.check_key_len(key)?;
+        self.check_value_len(value.len() as u64)?;
+        self.flush_if_due()?;
         util::handle(unsafe {
             logwriter_put(
-                self.0,
+                self.raw,
                 key.len() as u64,
                 key.as_ptr(),
                 value.len() as u64,
                 value.as_ptr(),
             )
-        })
-    }
+        })?;
+        self.unflushed += 1;
 
-    pub fn delete(&mut self, key: &[u8]) -> error::Result<()> {
-        util::handle(unsafe { logwriter_delete(self.0, key.len() as u64, key.as_ptr()) })
+        Ok(())
     }
 
-    pub fn flush(&mut self) -> error::Result<()> {
-        util::handle(unsafe { logwriter_flush(self.0) })
-    }
-}
+    /// Puts a value of exactly `len` bytes read from `value`.
+    ///
+    /// The C writer takes each value as a single buffer, so the value is still read into memory
+    /// once before being written, growing the buffer as it is read rather than trusting `len`
+    /// up front.  Fails without writing anything if `value` yields fewer or more than `len` bytes,
+    /// or with `TooLarge` if `len` doesn't fit in memory on this platform.
+    pub fn put_reader<R>(&mut self, key: &[u8], len: u64, mut value: R) -> error::Result<()>
+    where
+        R: io::Read,
+    {
+        use std::convert::TryFrom;
+        use std::io::Read;
 
-impl Drop for Writer {
-    fn drop(&mut self) {
-        util::handle(unsafe { logwriter_close(&mut self.0) }).unwrap()
-    }
-}
+        self.check_key_len(key)?;
+        self.check_value_len(len)?;
 
-unsafe impl Send for Writer {}
+        usize::try_from(len).map_err(|_| error::Error::TooLarge {
+            what: "value length",
+            value: len,
+            max: usize::MAX as u64,
+        })?;
 
-impl Reader {
-    pub fn open<P>(path: P) -> error::Result<Self>
-    where
-        P: AsRef<path::Path>,
-    {
-        let mut raw = ptr::null_mut();
-        let path = util::path_to_cstring(path)?;
+        let mut buf = Vec::new();
+        value
+            .by_ref()
+            .take(len)
+            .read_to_end(&mut buf)
+            .map_err(error::Error::IO)?;
+
+        if buf.len() as u64 != len {
+            return Err(error::Error::ValueTooShort {
+                expected: len,
+                actual: buf.len() as u64,
+            });
+        }
+        if value
+            .take(1)
+            .read_to_end(&mut buf)
+            .map_err(error::Error::IO)?
+            > 0
+        {
+            return Err(error::Error::ValueTooLong { expected: len });
+        }
+
+        self.put(key, &buf)
+    }
 
-        util::handle(unsafe { logreader_open(&mut raw, path.as_ptr()) })?;
+    pub fn delete(&mut self, key: &[u8]) -> error::Result<()> {
+        self.check_key_len(key)?;
+        self.flush_if_due()?;
+        util::handle(unsafe { logwriter_delete(self.raw, key.len() as u64, key.as_ptr()) })?;
+        self.unflushed += 1;
 
-        Ok(Self(raw, true))
+        Ok(())
     }
 
-    pub unsafe fn from_raw(raw: *mut logreader) -> Self {
-        Self(raw, false)
+    /// Flushes automatically once `interval` puts and deletes have been written since the last
+    /// flush, before writing the next one, so at most `interval` entries are lost on a crash.
+    /// `None`, the default, only flushes when asked to.
+    pub fn set_flush_interval(&mut self, interval: Option<u64>) {
+        self.flush_interval = interval;
     }
 
-    pub fn as_raw(&self) -> *mut logreader {
-        self.0
+    pub fn flush_interval(&self) -> Option<u64> {
+        self.flush_interval
+    }
+
+    /// Makes puts and deletes of keys longer than `cap` bytes fail with `TooLarge` without
+    /// writing anything.  `u64::MAX`, the default, allows any length.
+    pub fn set_max_key_len(&mut self, cap: u64) {
+        self.max_key_len = cap;
     }
 
     pub fn max_key_len(&self) -> u64 {
-        unsafe { logreader_maxkeylen(self.0) }
+        self.max_key_len
     }
 
-    pub fn max_value_len(&self) -> u64 {
-        unsafe { logreader_maxvaluelen(self.0) }
+    /// Makes puts of values longer than `cap` bytes fail with `TooLarge` without writing
+    /// anything, to catch values that were never meant to be stored.  `u64::MAX`, the default,
+    /// allows any length.
+    pub fn set_max_value_len(&mut self, cap: u64) {
+        self.max_value_len = cap;
     }
 
-    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
-    pub fn compression_block_size(&self) -> u32 {
-        unsafe { logreader_get_compression_blocksize(self.0) as u32 }
+    pub fn max_value_len(&self) -> u64 {
+        self.max_value_len
     }
 
-    pub fn compression_type(&self) -> CompressionType {
-        unsafe { CompressionType::from_raw(logreader_get_compression_type(self.0)) }
+    /// Writes a delete for `key` only if it is live in `hash`, returning whether it did.
+    ///
+    /// `hash` only knows about the log as it was when the index was written, so this does not see
+    /// puts or deletes made through this or any other writer since.
+    pub fn delete_if_present(&mut self, hash: &hash::Reader, key: &[u8]) -> error::Result<bool> {
+        if hash.contains_key(key)? {
+            self.delete(key)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
     }
 
-    pub fn entries(&self) -> error::Result<Entries> {
-        let mut raw = ptr::null_mut();
+    /// Puts a value made of `parts`, each prefixed with its length as a 4-byte little-endian
+    /// integer, so that `Entry::framed_parts` can split it back up.  Fails with `TooLarge` if a
+    /// part is 4 GiB or longer.
+    pub fn put_framed(&mut self, key: &[u8], parts: &[&[u8]]) -> error::Result<()> {
+        let value = util::framed::encode(parts)?;
+        self.put(key, &value)
+    }
 
-        util::handle(unsafe { logiter_create(&mut raw, self.0) })?;
+    pub fn put_entry(&mut self, entry: &Entry) -> error::Result<()> {
+        match entry.entry_type {
+            EntryType::Put => self.put(&entry.key, &entry.value),
+            EntryType::Delete => self.delete(&entry.key),
+        }
+    }
 
-        Ok(Entries(raw, self, None))
+    /// Writes every entry of `src` in order, puts and deletes alike, and returns how many it
+    /// wrote.  The entries are decompressed when read, so `src` may use a different compression
+    /// than this writer.
+    ///
+    /// The C library can only write entries from a key and a value, not copy encoded entries, so
+    /// each entry is still decoded and encoded again.  Keys and values are read into buffers that
+    /// are reused for every entry, so the copy doesn't allocate per entry.
+    pub fn append_from_reader(&mut self, src: &Reader) -> error::Result<u64> {
+        let mut copied = 0;
+
+        src.entries()?
+            .try_for_each_borrowed(|entry_type, key, value| {
+                match entry_type {
+                    EntryType::Put => self.put(key, value)?,
+                    EntryType::Delete => self.delete(key)?,
+                }
+                copied += 1;
+                Ok(())
+            })?;
+
+        Ok(copied)
     }
 
-    pub fn keys(&self) -> error::Result<Keys> {
-        let mut raw = ptr::null_mut();
+    /// Puts every pair in order.  If a put fails, the error is a `BatchFailed` with the index of
+    /// the failing pair; the pairs before it have been written.
+    pub fn put_many(&mut self, pairs: &[(&[u8], &[u8])]) -> error::Result<()> {
+        for (index, (key, value)) in pairs.iter().enumerate() {
+            if let Err(error) = self.put(key, value) {
+                return Err(error::Error::BatchFailed {
+                    index,
+                    error: Box::new(error),
+                });
+            }
+        }
 
-        util::handle(unsafe { logiter_create(&mut raw, self.0) })?;
+        Ok(())
+    }
+
+    pub fn extend_pairs<I, K, V>(&mut self, iter: I) -> error::Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        for (key, value) in iter {
+            self.put(key.as_ref(), value.as_ref())?;
+        }
 
-        Ok(Keys(raw, self, None))
+        Ok(())
     }
 
-    pub fn values(&self) -> error::Result<Values> {
-        let mut raw = ptr::null_mut();
+    /// Puts every key/value pair of a serialized map, as produced by serializing a `hash::Reader`.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_from<'de, D>(&mut self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(PairsVisitor(self))
+    }
 
-        util::handle(unsafe { logiter_create(&mut raw, self.0) })?;
+    /// Applies every CSV record from `input`, returning the number of records applied.  Fields
+    /// are quoted as described in RFC 4180, and blank lines are skipped.
+    ///
+    /// A `key,value` record is a put; a delete has an empty value and `delete` in a third column,
+    /// as in `key,,delete`.  Stops at the first invalid record with `Error::InvalidRecord`; the
+    /// records before it have already been written.
+    pub fn import_csv<R>(&mut self, input: R) -> error::Result<u64>
+    where
+        R: io::Read,
+    {
+        let mut input = io::BufReader::new(input);
+        let mut line = 0;
+        let mut applied = 0;
+
+        while let Some((start, fields)) = util::csv::read_record(&mut input, &mut line)? {
+            let invalid = |reason: &str| error::Error::InvalidRecord {
+                line: start,
+                reason: reason.to_owned(),
+            };
+
+            match fields.as_slice() {
+                [blank] if blank.is_empty() => continue,
+                [key, value] => self.put(key.as_bytes(), value.as_bytes())?,
+                [key, value, marker] if marker.is_empty() => {
+                    self.put(key.as_bytes(), value.as_bytes())?
+                }
+                [key, value, marker] if marker == "delete" => {
+                    if !value.is_empty() {
+                        return Err(invalid("a delete can't have a value"));
+                    }
+                    self.delete(key.as_bytes())?
+                }
+                [_, _, _] => return Err(invalid("the third column must be empty or `delete`")),
+                _ => return Err(invalid("expected 2 or 3 columns")),
+            }
+            applied += 1;
+        }
 
-        Ok(Values(raw, self, None))
+        Ok(applied)
     }
-}
 
-impl Drop for Reader {
-    fn drop(&mut self) {
-        if self.1 {
-            unsafe { logreader_close(&mut self.0) }
+    /// Applies every line of newline-delimited JSON from `input`, in the format described on
+    /// `NdjsonEncoding`, returning the number of records applied.  Blank lines are skipped.
+    ///
+    /// Stops at the first invalid line with `Error::InvalidRecord`; the records before it have
+    /// already been written.
+    #[cfg(feature = "ndjson")]
+    pub fn import_ndjson<R>(&mut self, input: R) -> error::Result<u64>
+    where
+        R: io::Read,
+    {
+        use std::io::BufRead;
+
+        let mut applied = 0;
+
+        for (i, line) in io::BufReader::new(input).lines().enumerate() {
+            let line = line.map_err(error::Error::IO)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let invalid = |reason| error::Error::InvalidRecord {
+                line: i as u64 + 1,
+                reason,
+            };
+            let record = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+            match util::ndjson::decode_record(record).map_err(invalid)? {
+                (EntryType::Put, key, value) => self.put(&key, &value)?,
+                (EntryType::Delete, key, _) => self.delete(&key)?,
+            }
+            applied += 1;
         }
+
+        Ok(applied)
     }
-}
 
-unsafe impl Send for Reader {}
+    /// Writes buffered entries to the file and updates the log header, making them visible to
+    /// readers opened afterwards.
+    ///
+    /// The data is only handed to the operating system, not synced to disk, so it can still be
+    /// lost if the machine crashes; use `sync_all` for that.
+    pub fn flush(&mut self) -> error::Result<()> {
+        util::handle(unsafe { logwriter_flush(self.raw) })?;
+        self.unflushed = 0;
+        self.flushes += 1;
 
-unsafe impl Sync for Reader {}
+        Ok(())
+    }
 
-impl<'a> Entries<'a> {
-    pub unsafe fn from_raw(
-        raw: *mut logiter,
-        reader: &'a Reader,
-        hash: Option<*mut hashreader>,
-    ) -> Entries<'a> {
-        Entries(raw, reader, hash)
+    /// The number of flushes this writer has issued, whether asked for with `flush` or
+    /// `sync_all` or due to the flush interval.  The final flush done by `close` is not counted.
+    pub fn flush_count(&self) -> u64 {
+        self.flushes
     }
 
-    pub fn as_raw(&self) -> *mut logiter {
-        self.0
+    /// Flushes like `flush`, then waits for the file's data and metadata to reach the disk with
+    /// `fsync`, so that the flushed entries survive a crash.
+    pub fn sync_all(&mut self) -> error::Result<()> {
+        self.flush()?;
+
+        if unsafe { libc::fsync(logwriter_fd(self.raw)) } != 0 {
+            return Err(error::Error::IO(io::Error::last_os_error()));
+        }
+
+        Ok(())
     }
 
-    #[allow(clippy::cast_possible_wrap)]
-    pub fn skip(&mut self, count: u32) -> error::Result<()> {
-        util::handle(unsafe { logiter_skip(self.0, (self.1).0, count as os::raw::c_int) })
+    /// Preallocates disk space for roughly `bytes` more bytes of log data.
+    ///
+    /// This is only a hint to the filesystem that can reduce fragmentation when the final size of
+    /// the log is known up front; the visible file size and the log contents are unchanged.  On
+    /// Linux the space is allocated with `fallocate(FALLOC_FL_KEEP_SIZE)`, and filesystems that
+    /// don't support it are silently skipped.  On other platforms this is a no-op.
+    pub fn reserve(&mut self, bytes: u64) -> error::Result<()> {
+        if bytes == 0 {
+            return Ok(());
+        }
+        self.reserve_impl(bytes)
     }
 
-    fn try_next(&mut self) -> error::Result<Option<Entry>> {
-        if let Some(hash) = self.2 {
-            util::handle(unsafe { logiter_hashnext(self.0, hash) })?;
-        } else {
-            util::handle(unsafe { logiter_next(self.0, (self.1).0) })?;
+    #[cfg(target_os = "linux")]
+    fn reserve_impl(&mut self, bytes: u64) -> error::Result<()> {
+        let too_large = || error::Error::TooLarge {
+            what: "reserved size",
+            value: bytes,
+            max: libc::off_t::MAX as u64,
+        };
+        if bytes > libc::off_t::MAX as u64 {
+            return Err(too_large());
         }
+        let len = bytes as libc::off_t;
 
-        match unsafe { logiter_state(self.0) } {
-            iter_state::ITER_ACTIVE => {
-                let entry_type = EntryType::from_raw(unsafe { logiter_type(self.0) });
-                let key = util::read_key(self.0, (self.1).0)?;
-                let value = util::read_value(self.0, (self.1).0)?;
+        let fd = unsafe { logwriter_fd(self.raw) };
+        let offset = unsafe { libc::lseek(fd, 0, libc::SEEK_CUR) };
+        if offset < 0 {
+            return Err(error::Error::IO(io::Error::last_os_error()));
+        }
+        offset.checked_add(len).ok_or_else(too_large)?;
 
-                Ok(Some(Entry {
-                    entry_type,
-                    key,
-                    value,
-                }))
+        if unsafe { libc::fallocate(fd, libc::FALLOC_FL_KEEP_SIZE, offset, len) } != 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => {}
+                _ => return Err(error::Error::IO(e)),
             }
-            _ => Ok(None),
         }
+
+        Ok(())
     }
-}
 
-impl fmt::Display for CompressionType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            CompressionType::None => f.write_str("none"),
-            CompressionType::Snappy => f.write_str("snappy"),
+    #[cfg(not(target_os = "linux"))]
+    fn reserve_impl(&mut self, _bytes: u64) -> error::Result<()> {
+        Ok(())
+    }
+
+    fn check_key_len(&self, key: &[u8]) -> error::Result<()> {
+        if key.len() as u64 > self.max_key_len {
+            return Err(error::Error::TooLarge {
+                what: "key length",
+                value: key.len() as u64,
+                max: self.max_key_len,
+            });
         }
+        Ok(())
     }
-}
 
-impl<'a> Iterator for Entries<'a> {
-    type Item = error::Result<Entry>;
+    fn check_value_len(&self, len: u64) -> error::Result<()> {
+        if len > self.max_value_len {
+            return Err(error::Error::TooLarge {
+                what: "value length",
+                value: len,
+                max: self.max_value_len,
+            });
+        }
+        Ok(())
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.try_next().transpose()
+    fn flush_if_due(&mut self) -> error::Result<()> {
+        match self.flush_interval {
+            Some(interval) if self.unflushed >= interval => self.flush(),
+            _ => Ok(()),
+        }
     }
-}
 
-impl<'a> Drop for Entries<'a> {
-    fn drop(&mut self) {
-        unsafe { logiter_close(&mut self.0) }
+    /// Flushes and closes the log.  Dropping the writer does the same, but can only log errors.
+    pub fn close(mut self) -> error::Result<()> {
+        util::handle(unsafe { logwriter_close(&mut self.raw) })
     }
 }
 
-unsafe impl<'a> Send for Entries<'a> {}
+#[cfg(feature = "serde")]
+struct PairsVisitor<'a>(&'a mut Writer);
 
-impl<'a> Keys<'a> {
-    pub unsafe fn from_raw(
-        raw: *mut logiter,
-        reader: &'a Reader,
-        hash: Option<*mut hashreader>,
-    ) -> Keys<'a> {
-        Keys(raw, reader, hash)
-    }
+#[cfg(feature = "serde")]
+impl<'a, 'de> serde::de::Visitor<'de> for PairsVisitor<'a> {
+    type Value = ();
 
-    pub fn as_raw(&self) -> *mut logiter {
-        self.0
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of keys to values")
     }
 
-    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
-    pub fn skip(&mut self, count: u32) -> error::Result<()> {
-        util::handle(unsafe { logiter_skip(self.0, (self.1).0, count as os::raw::c_int) })
-    }
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        use serde::de::Error;
+        use util::serde_bytes::Owned;
 
-    fn try_next(&mut self) -> error::Result<Option<bytes::BytesMut>> {
-        if let Some(hash) = self.2 {
-            util::handle(unsafe { logiter_hashnext(self.0, hash) })?;
-        } else {
-            util::handle(unsafe { logiter_next(self.0, (self.1).0) })?;
+        while let Some((Owned(key), Owned(value))) = map.next_entry()? {
+            self.0.put(&key, &value).map_err(A::Error::custom)?;
         }
 
-        match unsafe { logiter_state(self.0) } {
-            iter_state::ITER_ACTIVE => {
-                let key = util::read_key(self.0, (self.1).0)?;
+        Ok(())
+    }
+}
 
-                Ok(Some(key))
+impl Drop for Writer {
+    fn drop(&mut self) {
+        if !self.raw.is_null() {
+            if let Err(e) = util::handle(unsafe { logwriter_close(&mut self.raw) }) {
+                ::log::error!("failed to close log writer: {}", e);
             }
-            _ => Ok(None),
         }
     }
 }
 
-impl<'a> Iterator for Keys<'a> {
-    type Item = error::Result<bytes::BytesMut>;
+unsafe impl Send for Writer {}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.try_next().transpose()
+impl BufWriter {
+    /// Wraps `writer`, queueing up to `capacity` entries (at least one) between batches.  The
+    /// writer's flush interval is suspended until it is returned by `into_inner`.
+    pub fn new(mut writer: Writer, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let flush_interval = writer.flush_interval();
+        writer.set_flush_interval(None);
+
+        BufWriter {
+            writer,
+            flush_interval,
+            queue: Vec::with_capacity(capacity),
+            capacity,
+            batches: 0,
+        }
     }
-}
 
-impl<'a> Drop for Keys<'a> {
-    fn drop(&mut self) {
-        unsafe { logiter_close(&mut self.0) }
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> error::Result<()> {
+        self.push(EntryType::Put, key, value)
     }
-}
 
-unsafe impl<'a> Send for Keys<'a> {}
+    pub fn delete(&mut self, key: &[u8]) -> error::Result<()> {
+        self.push(EntryType::Delete, key, &[])
+    }
 
-impl<'a> Values<'a> {
-    pub unsafe fn from_raw(
-        raw: *mut logiter,
+    /// The number of entries waiting to be written.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The number of batches written to the underlying writer so far.
+    pub fn batches(&self) -> u64 {
+        self.batches
+    }
+
+    pub fn get_ref(&self) -> &Writer {
+        &self.writer
+    }
+
+    /// Writes all queued entries as a batch, which flushes the underlying writer.
+    pub fn flush(&mut self) -> error::Result<()> {
+        self.write_queue()
+    }
+
+    /// Writes all queued entries and returns the underlying writer with its flush interval
+    /// restored.
+    pub fn into_inner(mut self) -> error::Result<Writer> {
+        self.write_queue()?;
+
+        let mut writer = mem::replace(&mut self.writer, Writer::new(ptr::null_mut()));
+        writer.set_flush_interval(self.flush_interval);
+
+        Ok(writer)
+    }
+
+    /// Writes all queued entries and closes the log.
+    pub fn close(self) -> error::Result<()> {
+        self.into_inner()?.close()
+    }
+
+    fn push(&mut self, entry_type: EntryType, key: &[u8], value: &[u8]) -> error::Result<()> {
+        self.queue.push(Entry {
+            entry_type,
+            key: key.into(),
+            value: value.into(),
+        });
+
+        if self.queue.len() >= self.capacity {
+            self.write_queue()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_queue(&mut self) -> error::Result<()> {
+        if self.queue.is_empty() {
+            return Ok(());
+        }
+
+        for entry in self.queue.drain(..) {
+            self.writer.put_entry(&entry)?;
+        }
+        self.writer.flush()?;
+        self.batches += 1;
+
+        Ok(())
+    }
+}
+
+impl Drop for BufWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_queue() {
+            ::log::error!("failed to write queued log entries: {}", e);
+        }
+    }
+}
+
+impl WriteBatch {
+    /// Stages entries for `target` in an uncompressed temporary log.
+    pub fn new<P>(target: P) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        Self::new_with(target, WriterConfig::default())
+    }
+
+    /// Stages entries for `target` in a temporary log written with `config`, which is also the
+    /// configuration of the target if `commit` creates it.
+    pub fn new_with<P>(target: P, config: WriterConfig) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+
+        // Stage next to the target so that it can be renamed into place.
+        let target = target.as_ref().to_path_buf();
+        let mut path = target.as_os_str().to_os_string();
+        path.push(format!(
+            ".batch-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, atomic::Ordering::SeqCst)
+        ));
+        let path = path::PathBuf::from(path);
+        let writer = Writer::create_with(&path, config)?;
+
+        Ok(WriteBatch {
+            writer: Some(writer),
+            path,
+            target,
+        })
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> error::Result<()> {
+        self.writer().put(key, value)
+    }
+
+    pub fn delete(&mut self, key: &[u8]) -> error::Result<()> {
+        self.writer().delete(key)
+    }
+
+    /// Adds the staged entries to the target log, appending them if it exists and creating it
+    /// otherwise.  Either all of the entries are added or none are.
+    ///
+    /// The staged log is verified before the target is touched.  If appending fails partway, for
+    /// example because the disk is full, the target is truncated back to its old length and its
+    /// old header is restored.  The target is created by hard-linking the staged log, which fails
+    /// rather than replacing a target created concurrently; the entries are then appended to it.
+    pub fn commit(mut self) -> error::Result<()> {
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+
+        let staged = Reader::open(&self.path)?;
+        staged.verify()?;
+
+        match fs::hard_link(&self.path, &self.target) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                append_or_roll_back(&self.target, |writer| {
+                    writer.append_from_reader(&staged).map(drop)
+                })
+            }
+            Err(e) => Err(error::Error::IO(e)),
+        }
+    }
+
+    fn writer(&mut self) -> &mut Writer {
+        self.writer
+            .as_mut()
+            .expect("batch writer is only taken on commit")
+    }
+}
+
+impl Drop for WriteBatch {
+    fn drop(&mut self) {
+        self.writer.take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Appends to the existing log at `path` with `write`.  If that fails, the writer has already
+// flushed what it wrote when it was dropped, so the old length and header are written back.
+pub(crate) fn append_or_roll_back<F>(path: &path::Path, write: F) -> error::Result<()>
+where
+    F: FnOnce(&mut Writer) -> error::Result<()>,
+{
+    use std::io::{Read, Seek, Write};
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(error::Error::IO)?;
+    let len = file.metadata().map_err(error::Error::IO)?.len();
+    let mut header = [0; LOG_HEADER_SIZE as usize];
+    file.read_exact(&mut header).map_err(error::Error::IO)?;
+
+    let result = Writer::append(path).and_then(|mut writer| {
+        write(&mut writer)?;
+        writer.close()
+    });
+
+    if result.is_err() {
+        let restored = file
+            .set_len(len)
+            .and_then(|()| file.seek(io::SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(&header));
+        if let Err(e) = restored {
+            ::log::error!("failed to roll back {}: {}", path.display(), e);
+        }
+    }
+
+    result
+}
+
+impl Reader {
+    pub fn open<P>(path: P) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        let mut reader = Self(open_reader(path.as_ref())?, true, None);
+        reader.2 = Some(fs::canonicalize(path).map_err(error::Error::IO)?);
+
+        Ok(reader)
+    }
+
+    /// Opens the log at `log_path` together with the hash index at `hash_path`, ready for
+    /// lookups.  Fails with `FileIdentifierMismatch` if the index was built from a different log.
+    pub fn open_with_hash<P1, P2>(log_path: P1, hash_path: P2) -> error::Result<hash::Reader>
+    where
+        P1: AsRef<path::Path>,
+        P2: AsRef<path::Path>,
+    {
+        hash::Reader::open(hash_path, log_path)
+    }
+
+    /// Opens the log that `fd` refers to, even if it has since been renamed or unlinked.
+    ///
+    /// Sparkey can only open files by name, so this goes through `/proc/self/fd` and needs `/proc`
+    /// to be mounted.  The reader opens its own descriptor, so `fd` can be closed afterwards, but
+    /// it has no path to `reopen` or for `header_on_disk`.
+    #[cfg(target_os = "linux")]
+    pub fn from_fd(fd: os::unix::io::RawFd) -> error::Result<Self> {
+        let raw = open_reader(path::Path::new(&format!("/proc/self/fd/{}", fd)))?;
+
+        Ok(Self(raw, true, None))
+    }
+
+    /// # Safety
+    ///
+    /// `raw` must be a valid, open log reader that outlives the returned `Reader`; it will not be
+    /// closed when the `Reader` is dropped.
+    pub unsafe fn from_raw(raw: *mut logreader) -> Self {
+        Self(raw, false, None)
+    }
+
+    /// Like `from_raw`, but fails with `NullPointer` instead of wrapping a null pointer.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_raw`, if `raw` is not null.
+    pub unsafe fn from_raw_checked(raw: *mut logreader) -> error::Result<Self> {
+        if raw.is_null() {
+            Err(error::Error::NullPointer)
+        } else {
+            Ok(Self::from_raw(raw))
+        }
+    }
+
+    /// # Safety
+    ///
+    /// As for `from_raw`; `path` must be the file `raw` was opened from.
+    pub(crate) unsafe fn from_raw_with_path(raw: *mut logreader, path: path::PathBuf) -> Self {
+        Self(raw, false, Some(path))
+    }
+
+    /// Reopens the log file, making entries appended since it was opened visible to new
+    /// iterators.  Only possible for readers created through `open`.
+    pub fn reopen(&mut self) -> error::Result<()> {
+        let path = match (self.1, &self.2) {
+            (true, Some(path)) => path,
+            _ => return Err(error::Error::NoPath),
+        };
+        let mut raw = open_reader(path)?;
+
+        mem::swap(&mut self.0, &mut raw);
+        unsafe { logreader_close(&mut raw) };
+
+        Ok(())
+    }
+
+    pub fn as_raw(&self) -> *mut logreader {
+        self.0
+    }
+
+    /// The path of the log: canonicalized for readers created through `open`, and as given for
+    /// readers of a `hash::Reader`.  Readers created from raw pointers or descriptors have none.
+    pub fn path(&self) -> Option<&path::Path> {
+        self.2.as_deref()
+    }
+
+    pub fn max_key_len(&self) -> u64 {
+        unsafe { logreader_maxkeylen(self.0) }
+    }
+
+    pub fn max_value_len(&self) -> u64 {
+        unsafe { logreader_maxvaluelen(self.0) }
+    }
+
+    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    pub fn compression_block_size(&self) -> u32 {
+        unsafe { logreader_get_compression_blocksize(self.0) as u32 }
+    }
+
+    pub fn compression_type(&self) -> CompressionType {
+        unsafe { CompressionType::from_raw(logreader_get_compression_type(self.0)) }
+    }
+
+    /// The header the log had when this reader opened it, which bounds what it iterates over.
+    pub fn header(&self) -> LogHeader {
+        let mut raw = unsafe { mem::zeroed::<logheader>() };
+
+        unsafe { logreader_header(self.0, &mut raw) };
+
+        LogHeader::from_raw(&raw)
+    }
+
+    /// Reads the header of the log file again as it currently is on disk, which includes entries
+    /// appended since the reader was opened.  Needs the path of the log, so readers created
+    /// through `from_raw` or `from_fd` return `Error::NoPath`.
+    pub fn header_on_disk(&self) -> error::Result<LogHeader> {
+        let path = util::path_to_cstring(self.2.as_ref().ok_or(error::Error::NoPath)?)?;
+        let mut raw = unsafe { mem::zeroed::<logheader>() };
+
+        util::handle(unsafe { load_logheader(&mut raw, path.as_ptr()) })?;
+
+        Ok(LogHeader::from_raw(&raw))
+    }
+
+    /// The number of entries in the log according to the header this reader opened it with,
+    /// without iterating over it.  Entries appended since are not counted, as iterators don't
+    /// see them either.
+    pub fn entry_count(&self) -> u64 {
+        self.header().num_entries()
+    }
+
+    pub fn put_count(&self) -> u64 {
+        self.header().num_puts
+    }
+
+    pub fn delete_count(&self) -> u64 {
+        self.header().num_deletes
+    }
+
+    /// Scans the whole log once, reading keys but only the lengths of values.
+    ///
+    /// A log has no index of its keys, so counting live keys keeps a set of every live key in
+    /// memory; `hash::Reader::len` is much cheaper if only that count is needed.
+    pub fn stats(&self) -> error::Result<LogStats> {
+        let mut iter = self.keys()?.0;
+        let mut stats = LogStats::default();
+        let mut live = collections::HashSet::new();
+        let mut key = Vec::new();
+
+        while iter.advance()? {
+            let value_len = unsafe { logiter_valuelen(iter.raw) };
+            util::fill_key(iter.raw, iter.log, &mut key)?;
+            stats.total_key_len += key.len() as u64;
+
+            match EntryType::from_raw(unsafe { logiter_type(iter.raw) }) {
+                EntryType::Put => {
+                    stats.num_puts += 1;
+                    stats.total_value_len += value_len;
+                    stats.min_value_len =
+                        Some(stats.min_value_len.map_or(value_len, |n| n.min(value_len)));
+                    stats.max_value_len =
+                        Some(stats.max_value_len.map_or(value_len, |n| n.max(value_len)));
+                    if !live.contains(&key) {
+                        live.insert(key.clone());
+                    }
+                }
+                EntryType::Delete => {
+                    stats.num_deletes += 1;
+                    live.remove(&key);
+                }
+            }
+        }
+
+        stats.num_live_keys = live.len() as u64;
+
+        Ok(stats)
+    }
+
+    /// Reads every key and value in the log, so that each block is decompressed and each entry
+    /// is checked against the lengths in the header, stopping at the first inconsistency.
+    ///
+    /// The number of entries found is checked against the header the reader opened the log
+    /// with, so entries appended since are neither read nor expected.  The file the reader has
+    /// open is first checked to still reach the end of that header's data (truncation shrinks
+    /// the mapping, so reading it would crash).  Entry errors are reported as
+    /// `Error::LogEntryCorrupt` with the index of the entry.
+    pub fn verify(&self) -> error::Result<()> {
+        use std::os::unix::io::FromRawFd;
+
+        let header = self.header();
+        // Stat the reader's own descriptor, so this also works for readers without a path.
+        let file = mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(logreader_fd(self.0)) });
+        let len = file.metadata().map_err(error::Error::IO)?.len();
+        if len < header.data_end {
+            return Err(error::Error::LogTruncated {
+                len,
+                data_end: header.data_end,
+            });
+        }
+
+        let mut iter = self.entries()?.0;
+        let max_key_len = self.max_key_len();
+        let max_value_len = self.max_value_len();
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        let mut entry = 0;
+
+        let corrupt = |entry, error| error::Error::LogEntryCorrupt {
+            entry,
+            error: Box::new(error),
+        };
+
+        loop {
+            match iter.advance() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => return Err(corrupt(entry, e)),
+            }
+
+            let key_len = unsafe { logiter_keylen(iter.raw) };
+            let value_len = unsafe { logiter_valuelen(iter.raw) };
+            if key_len > max_key_len || value_len > max_value_len {
+                return Err(corrupt(entry, error::Error::LogHeaderCorrupt));
+            }
+
+            util::fill_key(iter.raw, iter.log, &mut key).map_err(|e| corrupt(entry, e))?;
+            util::fill_value(iter.raw, iter.log, &mut value).map_err(|e| corrupt(entry, e))?;
+            entry += 1;
+        }
+
+        if header.num_entries() != entry {
+            return Err(error::Error::LogEntryCountMismatch {
+                expected: header.num_entries(),
+                actual: entry,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Picks `n` entries uniformly at random, or all of them if the log has fewer, and returns
+    /// them in log order.  The same `seed` picks the same entries from the same log.
+    ///
+    /// This is a single scan with reservoir sampling, so it still reads through the whole log,
+    /// but only keeps the `n` picked entries in memory and only copies out their keys and values.
+    pub fn sample(&self, n: usize, seed: u64) -> error::Result<Vec<Entry>> {
+        let mut iter = self.entries()?.0;
+        let mut rng = util::SplitMix64::new(seed);
+        let mut picked: Vec<(u64, Entry)> = Vec::new();
+        let mut seen = 0;
+
+        while n > 0 && iter.advance()? {
+            let slot = if picked.len() < n {
+                Some(picked.len())
+            } else {
+                let slot = rng.below(seen + 1);
+                (slot < n as u64).then_some(slot as usize)
+            };
+
+            if let Some(slot) = slot {
+                let entry = (seen, iter.read_entry()?);
+                if slot == picked.len() {
+                    picked.push(entry);
+                } else {
+                    picked[slot] = entry;
+                }
+            }
+            seen += 1;
+        }
+
+        picked.sort_unstable_by_key(|&(index, _)| index);
+
+        Ok(picked.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// Scans the log for deletes, yielding each deleted key with the value it deleted, or `None`
+    /// if the key had no live value.  Keeps the latest value of every live key in memory.
+    pub fn deletions_with_prior(&self) -> error::Result<DeletionsWithPrior<'_>> {
+        Ok(DeletionsWithPrior {
+            entries: self.entries()?,
+            live: collections::HashMap::new(),
+        })
+    }
+
+    pub fn entries(&self) -> error::Result<Entries<'_>> {
+        let mut raw = ptr::null_mut();
+
+        util::handle(unsafe { logiter_create(&mut raw, self.0) })?;
+
+        Ok(Entries(Iter::new(raw, self.0, None), marker::PhantomData))
+    }
+
+    /// Iterates over all entries like `entries`, yielding `SharedEntry` values.
+    pub fn entries_shared(&self) -> error::Result<SharedEntries<'_>> {
+        Ok(SharedEntries(self.entries()?))
+    }
+
+    pub fn keys(&self) -> error::Result<Keys<'_>> {
+        let mut raw = ptr::null_mut();
+
+        util::handle(unsafe { logiter_create(&mut raw, self.0) })?;
+
+        Ok(Keys(Iter::new(raw, self.0, None), marker::PhantomData))
+    }
+
+    pub fn values(&self) -> error::Result<Values<'_>> {
+        let mut raw = ptr::null_mut();
+
+        util::handle(unsafe { logiter_create(&mut raw, self.0) })?;
+
+        Ok(Values(Iter::new(raw, self.0, None), marker::PhantomData))
+    }
+
+    /// Reads the key of every entry into memory, stopping at the first error.  Includes the keys
+    /// of deletes and overwritten entries, so this can need as much memory as the log's keys.
+    pub fn collect_keys(&self) -> error::Result<Vec<bytes::BytesMut>> {
+        self.keys()?.collect()
+    }
+
+    /// Reads the value of every entry into memory, stopping at the first error.  This can need as
+    /// much memory as the uncompressed log.
+    pub fn collect_values(&self) -> error::Result<Vec<bytes::BytesMut>> {
+        self.values()?.collect()
+    }
+}
+
+fn open_reader(path: &path::Path) -> error::Result<*mut logreader> {
+    let mut raw = ptr::null_mut();
+    let path = util::path_to_cstring(path)?;
+
+    util::handle(unsafe { logreader_open(&mut raw, path.as_ptr()) })?;
+
+    Ok(raw)
+}
+
+impl Builder {
+    pub fn try_from_iter<I, K, V>(iter: I) -> error::Result<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        static COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
+
+        let path = env::temp_dir().join(format!(
+            "sparkey-{}-{}.spl",
+            process::id(),
+            COUNTER.fetch_add(1, atomic::Ordering::SeqCst)
+        ));
+        let builder = Builder(path);
+
+        let mut writer = Writer::create(&builder.0, CompressionType::None, 0)?;
+        writer.extend_pairs(iter)?;
+        writer.flush()?;
+
+        Ok(builder)
+    }
+
+    pub fn path(&self) -> &path::Path {
+        &self.0
+    }
+
+    pub fn reader(&self) -> error::Result<Reader> {
+        Reader::open(&self.0)
+    }
+}
+
+impl iter::FromIterator<(Vec<u8>, Vec<u8>)> for Builder {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        Self::try_from_iter(iter).unwrap()
+    }
+}
+
+/// Puts every pair, panicking on the first error; use `Writer::extend_pairs` to handle errors.
+impl Extend<(Vec<u8>, Vec<u8>)> for Writer {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        self.extend_pairs(iter).unwrap()
+    }
+}
+
+impl Drop for Builder {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        if self.1 {
+            unsafe { logreader_close(&mut self.0) }
+        }
+    }
+}
+
+unsafe impl Send for Reader {}
+
+unsafe impl Sync for Reader {}
+
+/// Iterates over all entries, like `entries`.
+///
+/// Creating the underlying log iterator can fail, which `IntoIterator` has no way to report, so
+/// this panics in that case; use `entries` directly to handle the error instead.
+impl<'a> IntoIterator for &'a Reader {
+    type Item = error::Result<Entry>;
+    type IntoIter = Entries<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries().expect("failed to create log iterator")
+    }
+}
+
+impl<'a> Entries<'a> {
+    /// # Safety
+    ///
+    /// `raw` must be a valid log iterator created for `reader`, and `hash` (if any) must be a hash
+    /// reader for the same log.  The iterator will be closed when this value is dropped.
+    pub unsafe fn from_raw(
+        raw: *mut logiter,
+        reader: &'a Reader,
+        hash: Option<*mut hashreader>,
+    ) -> Entries<'a> {
+        Entries(Iter::new(raw, reader.0, hash), marker::PhantomData)
+    }
+
+    /// Like `from_raw`, but fails with `NullPointer` if `raw` or `hash` is null.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_raw`, if the pointers are not null.
+    pub unsafe fn from_raw_checked(
+        raw: *mut logiter,
+        reader: &'a Reader,
+        hash: Option<*mut hashreader>,
+    ) -> error::Result<Entries<'a>> {
+        if raw.is_null() || hash.is_some_and(|hash| hash.is_null()) {
+            Err(error::Error::NullPointer)
+        } else {
+            Ok(Self::from_raw(raw, reader, hash))
+        }
+    }
+
+    pub fn as_raw(&self) -> *mut logiter {
+        self.0.raw
+    }
+
+    /// Skips `count` entries; fails with `TooLarge` for counts the C API can't take (over
+    /// `i32::MAX`).
+    pub fn skip(&mut self, count: u32) -> error::Result<()> {
+        self.0.skip(count)
+    }
+
+    /// Reads keys and values into buffers from `pool` instead of allocating new ones.
+    pub fn set_pool(&mut self, pool: sync::Arc<dyn BytesPool + Send + Sync>) {
+        self.0.pool = Some(pool);
+    }
+
+    /// Skips `n` entries and returns the one after, like `Iterator::nth` but with errors
+    /// reported instead of yielded.  Returns `Ok(None)` if the log ends first.
+    ///
+    /// Skipped entries are not copied, and for iterators created through a `hash::Reader` only
+    /// live entries count, unlike with `skip`.
+    pub fn nth_entry(&mut self, n: u32) -> error::Result<Option<Entry>> {
+        for _ in 0..n {
+            if !self.0.advance()? {
+                return Ok(None);
+            }
+        }
+
+        self.try_next()
+    }
+
+    /// Returns an independent iterator at the same position, which yields the same remaining
+    /// entries as this one.
+    ///
+    /// This creates a new C iterator and seeks it to the current entry with `seek_offset`, so it
+    /// costs about as much as a lookup rather than a copy.
+    pub fn try_clone(&self) -> error::Result<Entries<'a>> {
+        Ok(Entries(self.0.try_clone()?, marker::PhantomData))
+    }
+
+    /// The key length of the entry the iterator was last advanced to, if any.
+    pub fn entry_key_len(&self) -> Option<u64> {
+        self.0
+            .is_active()
+            .then(|| unsafe { logiter_keylen(self.0.raw) })
+    }
+
+    /// The value length of the entry the iterator was last advanced to, if any.
+    pub fn entry_value_len(&self) -> Option<u64> {
+        self.0
+            .is_active()
+            .then(|| unsafe { logiter_valuelen(self.0.raw) })
+    }
+
+    /// The position of the entry the iterator was last advanced to, if any.
+    pub fn offset(&self) -> Option<Offset> {
+        self.0.offset.filter(|_| self.0.is_active())
+    }
+
+    pub fn state(&self) -> IterState {
+        IterState::from_raw(unsafe { logiter_state(self.0.raw) })
+    }
+
+    /// Streams the value of the entry the iterator was last advanced to.
+    pub fn value_chunks(&mut self) -> error::Result<ValueChunks<'_>> {
+        self.0.value_chunks()
+    }
+
+    /// Reads the value of the entry the iterator was last advanced to.
+    pub fn value_reader(&mut self) -> error::Result<ValueReader<'_>> {
+        self.0.value_reader()
+    }
+
+    /// Rewinds the iterator to the start of the log, reusing the underlying iterator.
+    pub fn reset(&mut self) -> error::Result<()> {
+        self.0.reset()
+    }
+
+    /// Positions the iterator at the entry at `offset`, as returned by `offset`, so that it is
+    /// yielded next.
+    ///
+    /// Fails with `InvalidOffset` if `offset` is outside the log, or if the entry found there is
+    /// inconsistent with the log, which catches most offsets that don't start an entry; the
+    /// iterator is then positioned at the end.
+    pub fn seek_offset(&mut self, offset: Offset) -> error::Result<()> {
+        self.0.seek_offset(offset)
+    }
+
+    /// Positions the iterator at the entry for `key`, so that it is yielded next.  Returns `false`
+    /// and positions the iterator at the end if the key is not live.  Requires an iterator
+    /// created through a `hash::Reader`.
+    pub fn seek(&mut self, key: &[u8]) -> error::Result<bool> {
+        self.0.seek(key)
+    }
+
+    /// Skips deletes, yielding the key and value of each remaining put.
+    pub fn puts_only(self) -> Puts<'a> {
+        Puts(self)
+    }
+
+    /// Skips puts, yielding the key of each remaining delete.  Values of puts are not read.
+    pub fn deletes_only(self) -> Deletes<'a> {
+        Deletes(self)
+    }
+
+    /// Yields the key and value of each remaining entry as strings, failing with `InvalidUtf8`
+    /// for keys or values that aren't UTF-8.  Deletes yield an empty value.
+    pub fn as_strings(self) -> Strings<'a> {
+        Strings(self)
+    }
+
+    /// Like `as_strings`, but replaces bytes that aren't UTF-8 with U+FFFD instead of failing.
+    pub fn as_strings_lossy(self) -> LossyStrings<'a> {
+        LossyStrings(self)
+    }
+
+    /// Counts the remaining entries, the bytes of their keys and values and the time spent
+    /// reading them, available from `WithMetrics::metrics`.  Iterators that aren't wrapped don't
+    /// pay for this.
+    pub fn with_metrics(self) -> WithMetrics<'a> {
+        WithMetrics(self, ScanMetrics::default())
+    }
+
+    /// Yields only the remaining entries whose key `predicate` returns `true` for.  Values are
+    /// only read for matching entries; the others are skipped without copying them.
+    pub fn filter_keys<F>(self, predicate: F) -> FilterKeys<'a, F>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        FilterKeys(self, predicate)
+    }
+
+    /// Calls `f` with each remaining entry's type, key and value, stopping at the first error.
+    ///
+    /// Keys and values are read into two buffers that are reused for every entry, so unlike
+    /// `next` this doesn't allocate per entry; the slices are only valid during the call.
+    pub fn for_each_borrowed<F>(&mut self, mut f: F) -> error::Result<()>
+    where
+        F: FnMut(EntryType, &[u8], &[u8]),
+    {
+        self.try_for_each_borrowed(|entry_type, key, value| {
+            f(entry_type, key, value);
+            Ok(())
+        })
+    }
+
+    /// Like `for_each_borrowed`, but also stops at the first error returned by `f`.
+    pub fn try_for_each_borrowed<F>(&mut self, mut f: F) -> error::Result<()>
+    where
+        F: FnMut(EntryType, &[u8], &[u8]) -> error::Result<()>,
+    {
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+
+        while self.0.advance()? {
+            let entry_type = EntryType::from_raw(unsafe { logiter_type(self.0.raw) });
+            util::fill_key(self.0.raw, self.0.log, &mut key)?;
+            util::fill_value(self.0.raw, self.0.log, &mut value)?;
+
+            f(entry_type, &key, &value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances to the next entry and reads its key, leaving the value to be read through
+    /// `LazyValue::as_bytes` if needed.
+    ///
+    /// Values that are never accessed are skipped without being copied out of the log.  Compressed
+    /// blocks are still decompressed as the iterator passes through them, since the C library
+    /// decompresses whole blocks at a time.
+    pub fn next_lazy(&mut self) -> error::Result<Option<LazyEntry<'_>>> {
+        if self.0.advance()? {
+            let entry_type = EntryType::from_raw(unsafe { logiter_type(self.0.raw) });
+            let key = self.0.read_key()?;
+
+            Ok(Some(LazyEntry {
+                entry_type,
+                key,
+                value: LazyValue(&mut self.0, None),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn try_next(&mut self) -> error::Result<Option<Entry>> {
+        if self.0.advance()? {
+            Ok(Some(self.0.read_entry()?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = error::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for Entries<'a> {}
+
+impl OwnedEntries {
+    /// Iterates over the live entries of the log behind `reader`, keeping the reader alive for as
+    /// long as the iterator.
+    pub fn entries_from_hash(reader: hash::Reader) -> error::Result<Self> {
+        // The iterator only holds pointers to the C readers, which stay put when `reader` is
+        // moved, and the field order drops it before them.
+        let Entries(iter, _) = reader.entries()?;
+
+        Ok(OwnedEntries(Entries(iter, marker::PhantomData), reader))
+    }
+
+    pub fn hash_reader(&self) -> &hash::Reader {
+        &self.1
+    }
+
+    /// Stops iterating and returns the hash reader.
+    pub fn into_hash_reader(self) -> hash::Reader {
+        let OwnedEntries(entries, reader) = self;
+        drop(entries);
+        reader
+    }
+
+    /// Skips `count` entries, as `Entries::skip`.
+    pub fn skip(&mut self, count: u32) -> error::Result<()> {
+        Entries::skip(&mut self.0, count)
+    }
+
+    /// The position of the entry the iterator was last advanced to, if any.
+    pub fn offset(&self) -> Option<Offset> {
+        self.0.offset()
+    }
+
+    /// Rewinds the iterator to the start of the log.
+    pub fn reset(&mut self) -> error::Result<()> {
+        self.0.reset()
+    }
+
+    /// Positions the iterator at the entry at `offset`, as `Entries::seek_offset`.
+    pub fn seek_offset(&mut self, offset: Offset) -> error::Result<()> {
+        self.0.seek_offset(offset)
+    }
+
+    /// Positions the iterator at the entry for `key`, as `Entries::seek`.
+    pub fn seek(&mut self, key: &[u8]) -> error::Result<bool> {
+        self.0.seek(key)
+    }
+}
+
+impl Iterator for OwnedEntries {
+    type Item = error::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl iter::FusedIterator for OwnedEntries {}
+
+impl LogStats {
+    /// The mean value length of all puts, if there are any.
+    pub fn mean_value_len(&self) -> Option<f64> {
+        if self.num_puts == 0 {
+            None
+        } else {
+            Some(self.total_value_len as f64 / self.num_puts as f64)
+        }
+    }
+}
+
+impl LogHeader {
+    fn from_raw(raw: &logheader) -> Self {
+        LogHeader {
+            major_version: raw.major_version,
+            minor_version: raw.minor_version,
+            file_identifier: raw.file_identifier,
+            num_puts: raw.num_puts,
+            num_deletes: raw.num_deletes,
+            data_end: raw.data_end,
+            max_key_len: raw.max_key_len,
+            max_value_len: raw.max_value_len,
+            put_size: raw.put_size,
+            delete_size: raw.delete_size,
+            compression_type: CompressionType::from_raw(raw.compression_type),
+            compression_block_size: raw.compression_block_size,
+            max_entries_per_block: raw.max_entries_per_block,
+        }
+    }
+
+    /// The number of entries in the log, puts and deletes alike.
+    pub fn num_entries(&self) -> u64 {
+        self.num_puts + self.num_deletes
+    }
+}
+
+impl fmt::Display for CompressionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompressionType::None => f.write_str("none"),
+            CompressionType::Snappy => f.write_str("snappy"),
+        }
+    }
+}
+
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EntryType::Put => f.write_str("put"),
+            EntryType::Delete => f.write_str("delete"),
+        }
+    }
+}
+
+impl<'a> Keys<'a> {
+    /// # Safety
+    ///
+    /// `raw` must be a valid log iterator created for `reader`, and `hash` (if any) must be a hash
+    /// reader for the same log.  The iterator will be closed when this value is dropped.
+    pub unsafe fn from_raw(
+        raw: *mut logiter,
+        reader: &'a Reader,
+        hash: Option<*mut hashreader>,
+    ) -> Keys<'a> {
+        Keys(Iter::new(raw, reader.0, hash), marker::PhantomData)
+    }
+
+    /// Like `from_raw`, but fails with `NullPointer` if `raw` or `hash` is null.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_raw`, if the pointers are not null.
+    pub unsafe fn from_raw_checked(
+        raw: *mut logiter,
+        reader: &'a Reader,
+        hash: Option<*mut hashreader>,
+    ) -> error::Result<Keys<'a>> {
+        if raw.is_null() || hash.is_some_and(|hash| hash.is_null()) {
+            Err(error::Error::NullPointer)
+        } else {
+            Ok(Self::from_raw(raw, reader, hash))
+        }
+    }
+
+    pub fn as_raw(&self) -> *mut logiter {
+        self.0.raw
+    }
+
+    /// Skips `count` entries; fails with `TooLarge` for counts the C API can't take (over
+    /// `i32::MAX`).
+    pub fn skip(&mut self, count: u32) -> error::Result<()> {
+        self.0.skip(count)
+    }
+
+    /// Reads keys into buffers from `pool` instead of allocating new ones.
+    pub fn set_pool(&mut self, pool: sync::Arc<dyn BytesPool + Send + Sync>) {
+        self.0.pool = Some(pool);
+    }
+
+    /// The key length of the entry the iterator was last advanced to, if any.
+    pub fn entry_key_len(&self) -> Option<u64> {
+        self.0
+            .is_active()
+            .then(|| unsafe { logiter_keylen(self.0.raw) })
+    }
+
+    /// The value length of the entry the iterator was last advanced to, if any.
+    pub fn entry_value_len(&self) -> Option<u64> {
+        self.0
+            .is_active()
+            .then(|| unsafe { logiter_valuelen(self.0.raw) })
+    }
+
+    pub fn state(&self) -> IterState {
+        IterState::from_raw(unsafe { logiter_state(self.0.raw) })
+    }
+
+    /// Streams the value of the entry the iterator was last advanced to.
+    pub fn value_chunks(&mut self) -> error::Result<ValueChunks<'_>> {
+        self.0.value_chunks()
+    }
+
+    /// Reads the value of the entry the iterator was last advanced to.
+    pub fn value_reader(&mut self) -> error::Result<ValueReader<'_>> {
+        self.0.value_reader()
+    }
+
+    /// Rewinds the iterator to the start of the log, reusing the underlying iterator.
+    pub fn reset(&mut self) -> error::Result<()> {
+        self.0.reset()
+    }
+
+    /// Positions the iterator at the entry for `key`, so that it is yielded next.  Returns `false`
+    /// and positions the iterator at the end if the key is not live.  Requires an iterator
+    /// created through a `hash::Reader`.
+    pub fn seek(&mut self, key: &[u8]) -> error::Result<bool> {
+        self.0.seek(key)
+    }
+
+    fn try_next(&mut self) -> error::Result<Option<bytes::BytesMut>> {
+        if self.0.advance()? {
+            let key = self.0.read_key()?;
+
+            Ok(Some(key))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = error::Result<bytes::BytesMut>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for Keys<'a> {}
+
+impl<'a> Values<'a> {
+    /// # Safety
+    ///
+    /// `raw` must be a valid log iterator created for `reader`, and `hash` (if any) must be a hash
+    /// reader for the same log.  The iterator will be closed when this value is dropped.
+    pub unsafe fn from_raw(
+        raw: *mut logiter,
         reader: &'a Reader,
         hash: Option<*mut hashreader>,
     ) -> Values<'a> {
-        Values(raw, reader, hash)
+        Values(Iter::new(raw, reader.0, hash), marker::PhantomData)
+    }
+
+    /// Like `from_raw`, but fails with `NullPointer` if `raw` or `hash` is null.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_raw`, if the pointers are not null.
+    pub unsafe fn from_raw_checked(
+        raw: *mut logiter,
+        reader: &'a Reader,
+        hash: Option<*mut hashreader>,
+    ) -> error::Result<Values<'a>> {
+        if raw.is_null() || hash.is_some_and(|hash| hash.is_null()) {
+            Err(error::Error::NullPointer)
+        } else {
+            Ok(Self::from_raw(raw, reader, hash))
+        }
     }
 
     pub fn as_raw(&self) -> *mut logiter {
-        self.0
+        self.0.raw
     }
 
-    #[allow(clippy::cast_possible_wrap)]
+    /// Skips `count` entries; fails with `TooLarge` for counts the C API can't take (over
+    /// `i32::MAX`).
     pub fn skip(&mut self, count: u32) -> error::Result<()> {
-        util::handle(unsafe { logiter_skip(self.0, (self.1).0, count as os::raw::c_int) })
+        self.0.skip(count)
+    }
+
+    /// Reads values into buffers from `pool` instead of allocating new ones.
+    pub fn set_pool(&mut self, pool: sync::Arc<dyn BytesPool + Send + Sync>) {
+        self.0.pool = Some(pool);
+    }
+
+    /// The key length of the entry the iterator was last advanced to, if any.
+    pub fn entry_key_len(&self) -> Option<u64> {
+        self.0
+            .is_active()
+            .then(|| unsafe { logiter_keylen(self.0.raw) })
+    }
+
+    /// The value length of the entry the iterator was last advanced to, if any.
+    pub fn entry_value_len(&self) -> Option<u64> {
+        self.0
+            .is_active()
+            .then(|| unsafe { logiter_valuelen(self.0.raw) })
+    }
+
+    pub fn state(&self) -> IterState {
+        IterState::from_raw(unsafe { logiter_state(self.0.raw) })
+    }
+
+    /// Streams the value of the entry the iterator was last advanced to.
+    pub fn value_chunks(&mut self) -> error::Result<ValueChunks<'_>> {
+        self.0.value_chunks()
+    }
+
+    /// Reads the value of the entry the iterator was last advanced to.
+    pub fn value_reader(&mut self) -> error::Result<ValueReader<'_>> {
+        self.0.value_reader()
+    }
+
+    /// Rewinds the iterator to the start of the log, reusing the underlying iterator.
+    pub fn reset(&mut self) -> error::Result<()> {
+        self.0.reset()
+    }
+
+    /// Positions the iterator at the entry for `key`, so that it is yielded next.  Returns `false`
+    /// and positions the iterator at the end if the key is not live.  Requires an iterator
+    /// created through a `hash::Reader`.
+    pub fn seek(&mut self, key: &[u8]) -> error::Result<bool> {
+        self.0.seek(key)
     }
 
     fn try_next(&mut self) -> error::Result<Option<bytes::BytesMut>> {
-        if let Some(hash) = self.2 {
-            util::handle(unsafe { logiter_hashnext(self.0, hash) })?;
+        if self.0.advance()? {
+            let value = self.0.read_value()?;
+
+            Ok(Some(value))
         } else {
-            util::handle(unsafe { logiter_next(self.0, (self.1).0) })?;
+            Ok(None)
         }
+    }
+}
 
-        match unsafe { logiter_state(self.0) } {
-            iter_state::ITER_ACTIVE => {
-                let value = util::read_value(self.0, (self.1).0)?;
+impl<'a> Iterator for SharedEntries<'a> {
+    type Item = error::Result<SharedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|e| e.map(From::from))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for SharedEntries<'a> {}
+
+impl<'a> Iterator for Puts<'a> {
+    type Item = error::Result<(bytes::BytesMut, bytes::BytesMut)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.0.next()? {
+                Ok(Entry {
+                    entry_type: EntryType::Put,
+                    key,
+                    value,
+                }) => return Some(Ok((key, value))),
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl<'a> iter::FusedIterator for Puts<'a> {}
+
+impl<'a> Deletes<'a> {
+    fn try_next(&mut self) -> error::Result<Option<bytes::BytesMut>> {
+        let iter = &mut (self.0).0;
+
+        while iter.advance()? {
+            if let entry_type::ENTRY_DELETE = unsafe { logiter_type(iter.raw) } {
+                return Ok(Some(iter.read_key()?));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for Deletes<'a> {
+    type Item = error::Result<bytes::BytesMut>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+}
+
+impl<'a> iter::FusedIterator for Deletes<'a> {}
+
+impl<'a, F> FilterKeys<'a, F>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    fn try_next(&mut self) -> error::Result<Option<Entry>> {
+        let iter = &mut (self.0).0;
+
+        while iter.advance()? {
+            let key = iter.read_key()?;
+
+            if (self.1)(&key) {
+                let entry_type = EntryType::from_raw(unsafe { logiter_type(iter.raw) });
+                let value = iter.read_value()?;
+
+                return Ok(Some(Entry {
+                    entry_type,
+                    key,
+                    value,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<'a, F> Iterator for FilterKeys<'a, F>
+where
+    F: FnMut(&[u8]) -> bool,
+{
+    type Item = error::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+}
+
+impl<'a, F> iter::FusedIterator for FilterKeys<'a, F> where F: FnMut(&[u8]) -> bool {}
+
+impl<'a> LazyValue<'a> {
+    /// The length of the value, known without reading it.
+    pub fn len(&self) -> u64 {
+        unsafe { logiter_valuelen(self.0.raw) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the value on first use and returns it.
+    pub fn as_bytes(&mut self) -> error::Result<&[u8]> {
+        if self.1.is_none() {
+            self.1 = Some(self.0.read_value()?);
+        }
+
+        Ok(self.1.as_ref().unwrap())
+    }
+
+    /// Reads the value unless it has been already, and returns it.
+    pub fn into_bytes(mut self) -> error::Result<bytes::BytesMut> {
+        match self.1.take() {
+            Some(value) => Ok(value),
+            None => self.0.read_value(),
+        }
+    }
+}
+
+impl<'a> Iterator for Strings<'a> {
+    type Item = error::Result<(String, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| {
+            let entry = entry?;
+            let key = util::to_str("key", &entry.key)?;
+            let value = util::to_str("value", &entry.value)?;
+
+            Ok((key.to_owned(), value.to_owned()))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for Strings<'a> {}
+
+impl<'a> Iterator for LossyStrings<'a> {
+    type Item = error::Result<(String, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| {
+            let entry = entry?;
+            let key = String::from_utf8_lossy(&entry.key).into_owned();
+            let value = String::from_utf8_lossy(&entry.value).into_owned();
+
+            Ok((key, value))
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for LossyStrings<'a> {}
+
+impl<'a> WithMetrics<'a> {
+    /// The metrics of the entries yielded so far.
+    pub fn metrics(&self) -> ScanMetrics {
+        self.1
+    }
+
+    pub fn into_inner(self) -> Entries<'a> {
+        self.0
+    }
+}
+
+impl<'a> Iterator for WithMetrics<'a> {
+    type Item = error::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = time::Instant::now();
+        let next = self.0.next();
+        self.1.elapsed += start.elapsed();
 
-                Ok(Some(value))
+        if let Some(Ok(ref entry)) = next {
+            self.1.entries += 1;
+            self.1.bytes_read += (entry.key.len() + entry.value.len()) as u64;
+        }
+
+        next
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for WithMetrics<'a> {}
+
+impl Entry {
+    /// Compares the keys of two entries bytewise, ignoring their types and values.
+    pub fn cmp_key(&self, other: &Entry) -> cmp::Ordering {
+        self.key[..].cmp(&other.key[..])
+    }
+
+    /// Splits the entry into its key and value.
+    pub fn into_pair(self) -> (bytes::BytesMut, bytes::BytesMut) {
+        (self.key, self.value)
+    }
+
+    /// Splits a value written by `Writer::put_framed` into its parts.  Fails with `InvalidFrame`
+    /// if the value isn't made of length-prefixed parts.
+    pub fn framed_parts(&self) -> error::Result<Vec<&[u8]>> {
+        util::framed::decode(&self.value)
+    }
+
+    pub fn key_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(&self.key)
+    }
+
+    pub fn value_str(&self) -> Result<&str, str::Utf8Error> {
+        str::from_utf8(&self.value)
+    }
+}
+
+impl<'a> Iterator for DeletionsWithPrior<'a> {
+    type Item = error::Result<(bytes::BytesMut, Option<bytes::BytesMut>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next()? {
+                Ok(Entry {
+                    entry_type: EntryType::Put,
+                    key,
+                    value,
+                }) => {
+                    self.live.insert(key.to_vec(), value);
+                }
+                Ok(Entry {
+                    entry_type: EntryType::Delete,
+                    key,
+                    ..
+                }) => {
+                    let prior = self.live.remove(&key[..]);
+                    return Some(Ok((key, prior)));
+                }
+                Err(e) => return Some(Err(e)),
             }
-            _ => Ok(None),
+        }
+    }
+}
+
+impl<'a> iter::FusedIterator for DeletionsWithPrior<'a> {}
+
+impl From<Entry> for SharedEntry {
+    fn from(entry: Entry) -> Self {
+        SharedEntry {
+            entry_type: entry.entry_type,
+            key: entry.key.freeze(),
+            value: entry.value.freeze(),
         }
     }
 }
@@ -381,12 +2387,305 @@ impl<'a> Iterator for Values<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.try_next().transpose()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> iter::FusedIterator for Values<'a> {}
+
+impl Iter {
+    fn new(raw: *mut logiter, log: *mut logreader, hash: Option<*mut hashreader>) -> Self {
+        Iter {
+            raw,
+            log,
+            hash,
+            pending: false,
+            remaining: hash.map(|hash| unsafe { hash_numentries(hash) }),
+            offset: None,
+            pool: None,
+        }
+    }
+
+    fn skip(&mut self, count: u32) -> error::Result<()> {
+        let count = util::to_c_int("skip count", count)?;
+
+        self.pending = false;
+        self.remaining = None;
+        util::handle(unsafe { logiter_skip(self.raw, self.log, count) })?;
+        self.offset = Some(self.raw_offset());
+
+        Ok(())
+    }
+
+    fn reset(&mut self) -> error::Result<()> {
+        // `logiter_reset` only rewinds the current entry, so seek back to the first block instead.
+        self.pending = false;
+        self.remaining = self.hash.map(|hash| unsafe { hash_numentries(hash) });
+        util::handle(unsafe { logiter_seek(self.raw, self.log, LOG_HEADER_SIZE) })
+    }
+
+    fn read_entry(&self) -> error::Result<Entry> {
+        let entry_type = EntryType::from_raw(unsafe { logiter_type(self.raw) });
+        let key = self.read_key()?;
+        let value = self.read_value()?;
+
+        Ok(Entry {
+            entry_type,
+            key,
+            value,
+        })
+    }
+
+    fn read_key(&self) -> error::Result<bytes::BytesMut> {
+        let buf = self.acquire(unsafe { logiter_keylen(self.raw) });
+        util::read_key_into(self.raw, self.log, buf)
+    }
+
+    fn read_value(&self) -> error::Result<bytes::BytesMut> {
+        let buf = self.acquire(unsafe { logiter_valuelen(self.raw) });
+        util::read_value_into(self.raw, self.log, buf)
+    }
+
+    fn acquire(&self, len: u64) -> bytes::BytesMut {
+        use std::convert::TryFrom;
+
+        match self.pool {
+            Some(ref pool) => pool.acquire(usize::try_from(len).unwrap()),
+            None => bytes::BytesMut::new(),
+        }
+    }
+
+    fn try_clone(&self) -> error::Result<Iter> {
+        let mut raw = ptr::null_mut();
+        util::handle(unsafe { logiter_create(&mut raw, self.log) })?;
+        let mut clone = Iter::new(raw, self.log, self.hash);
+
+        match (unsafe { logiter_state(self.raw) }, self.offset) {
+            (iter_state::ITER_NEW, _) => {}
+            (iter_state::ITER_ACTIVE, Some(offset)) => {
+                clone.seek_offset(offset)?;
+                // Unless the current entry is still to be yielded, move past it on the next call.
+                clone.pending = self.pending;
+            }
+            _ => {
+                let data_end = unsafe { logreader_data_end(self.log) };
+                util::handle(unsafe { logiter_seek(clone.raw, clone.log, data_end) })?;
+            }
+        }
+        clone.remaining = self.remaining;
+        clone.pool = self.pool.clone();
+
+        Ok(clone)
+    }
+
+    fn seek(&mut self, key: &[u8]) -> error::Result<bool> {
+        let hash = self.hash.ok_or(error::Error::NoHashIndex)?;
+
+        self.pending = false;
+        self.remaining = None;
+        util::handle(unsafe { hash_get(hash, key.as_ptr(), key.len() as u64, self.raw) })?;
+
+        match unsafe { logiter_state(self.raw) } {
+            iter_state::ITER_ACTIVE => {
+                self.offset = Some(self.raw_offset());
+                self.pending = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn seek_offset(&mut self, offset: Offset) -> error::Result<()> {
+        let data_end = unsafe { logreader_data_end(self.log) };
+
+        self.pending = false;
+        self.remaining = None;
+
+        let found = offset.block_position >= LOG_HEADER_SIZE
+            && offset.block_position < data_end
+            && util::to_c_int("entry index", offset.entry_index)
+                .and_then(|entry_index| {
+                    util::handle(unsafe {
+                        logiter_seek(self.raw, self.log, offset.block_position)
+                    })?;
+                    util::handle(unsafe { logiter_skip(self.raw, self.log, entry_index) })
+                })
+                .and_then(|_| util::handle(unsafe { logiter_next(self.raw, self.log) }))
+                .is_ok()
+            && self.is_active()
+            && self.raw_offset() == offset
+            && self.is_consistent(data_end);
+
+        if found {
+            self.offset = Some(offset);
+            self.pending = true;
+            Ok(())
+        } else {
+            // Seeking to the end of the data closes the iterator.
+            util::handle(unsafe { logiter_seek(self.raw, self.log, data_end) })?;
+            Err(error::Error::InvalidOffset { offset })
+        }
+    }
+
+    // Checks the lengths of the current entry against the log.
+    fn is_consistent(&self, data_end: u64) -> bool {
+        let key_len = unsafe { logiter_keylen(self.raw) };
+        let value_len = unsafe { logiter_valuelen(self.raw) };
+        let block_position = unsafe { logiter_entry_block_position(self.raw) };
+
+        key_len <= unsafe { logreader_maxkeylen(self.log) }
+            && value_len <= unsafe { logreader_maxvaluelen(self.log) }
+            && match CompressionType::from_raw(unsafe { logreader_get_compression_type(self.log) })
+            {
+                // Uncompressed entries are contiguous, so they must end within the data.
+                CompressionType::None => key_len
+                    .checked_add(value_len)
+                    .and_then(|len| len.checked_add(block_position))
+                    .is_some_and(|end| end <= data_end),
+                CompressionType::Snappy => true,
+            }
+    }
+
+    fn advance(&mut self) -> error::Result<bool> {
+        let was_pending = self.pending;
+
+        if self.pending {
+            // The lookup consumed the key while comparing it; rewind so it can be read again.
+            util::handle(unsafe { logiter_reset(self.raw, self.log) })?;
+            self.pending = false;
+        } else {
+            // A failed lookup or read leaves the iterator at an undefined position.
+            if let iter_state::ITER_INVALID = unsafe { logiter_state(self.raw) } {
+                return Ok(false);
+            }
+
+            if let Some(hash) = self.hash {
+                util::handle(unsafe { logiter_hashnext(self.raw, hash) })?;
+            } else {
+                util::handle(unsafe { logiter_next(self.raw, self.log) })?;
+            }
+        }
+
+        match unsafe { logiter_state(self.raw) } {
+            iter_state::ITER_ACTIVE => {
+                if !was_pending {
+                    self.offset = Some(self.raw_offset());
+                }
+                self.remaining = self.remaining.map(|n| n.saturating_sub(1));
+                Ok(true)
+            }
+            _ => {
+                self.remaining = self.remaining.map(|_| 0);
+                Ok(false)
+            }
+        }
+    }
+
+    // Only accurate until the entry is read: crossing into the next block resets the index.
+    #[allow(clippy::cast_sign_loss)]
+    fn raw_offset(&self) -> Offset {
+        Offset {
+            block_position: unsafe { logiter_entry_block_position(self.raw) },
+            entry_index: unsafe { logiter_entry_index(self.raw) } as u32,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(unsafe { logiter_state(self.raw) }, iter_state::ITER_ACTIVE)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        use std::convert::TryFrom;
+
+        match self.remaining {
+            Some(n) => {
+                let n = usize::try_from(n).unwrap_or(usize::MAX);
+                (n, Some(n))
+            }
+            None => (0, None),
+        }
+    }
+
+    fn value_chunks(&mut self) -> error::Result<ValueChunks<'_>> {
+        util::handle(unsafe { logiter_reset(self.raw, self.log) })?;
+
+        Ok(ValueChunks(self))
+    }
+
+    fn value_reader(&mut self) -> error::Result<ValueReader<'_>> {
+        util::handle(unsafe { logiter_reset(self.raw, self.log) })?;
+        let len = unsafe { logiter_valuelen(self.raw) };
+
+        Ok(ValueReader(self, len))
+    }
 }
 
-impl<'a> Drop for Values<'a> {
+impl Drop for Iter {
     fn drop(&mut self) {
-        unsafe { logiter_close(&mut self.0) }
+        unsafe { logiter_close(&mut self.raw) }
+    }
+}
+
+unsafe impl Send for Iter {}
+
+impl<'a> ValueChunks<'a> {
+    /// Returns the next chunk of the value, or `None` once all of it has been read.  This is not an
+    /// `Iterator` since each chunk borrows a block buffer that is reused by the next call.
+    pub fn next_chunk(&mut self) -> error::Result<Option<&[u8]>> {
+        use std::convert::TryFrom;
+
+        let mut chunk = ptr::null_mut();
+        let mut len = 0;
+
+        util::handle(unsafe {
+            logiter_valuechunk(self.0.raw, self.0.log, u64::MAX, &mut chunk, &mut len)
+        })?;
+
+        if len == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(unsafe {
+                slice::from_raw_parts(chunk, usize::try_from(len).unwrap())
+            }))
+        }
+    }
+}
+
+impl<'a> ValueReader<'a> {
+    /// The number of value bytes that have not been read yet.
+    pub fn remaining(&self) -> u64 {
+        self.1
     }
 }
 
-unsafe impl<'a> Send for Values<'a> {}
+impl<'a> io::Read for ValueReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::convert::TryFrom;
+
+        if self.1 == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut chunk = ptr::null_mut();
+        let mut len = 0;
+
+        util::handle(unsafe {
+            logiter_valuechunk(
+                (self.0).raw,
+                (self.0).log,
+                buf.len() as u64,
+                &mut chunk,
+                &mut len,
+            )
+        })
+        .map_err(|e| io::Error::other(e.compat()))?;
+
+        let len = usize::try_from(len).unwrap();
+        buf[..len].copy_from_slice(unsafe { slice::from_raw_parts(chunk, len) });
+        self.1 -= len as u64;
+
+        Ok(len)
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections;
+use std::path;
+
+use crate::error;
+use crate::hash;
+use crate::log;
+
+/// A sparkey database at `<base>.spl` and `<base>.spi`, accessed through its hash index.
+///
+/// `create` returns a `Writer`; the hash index is only written by `Writer::finalize` or
+/// `Writer::close`, so a database that was never finalized can't be opened.
+///
+/// One writer can add to a database while readers query it: readers from `open` and
+/// `open_readonly` see the database as it was when they were opened, and the writer from `append`
+/// only adds to the end of the log and replaces the index atomically on `finalize`.  Readers
+/// opened after that see the new data.  `create` truncates the log instead, so it must not be used
+/// while readers are open.
+///
+/// A `Map` can be cloned and shared between threads; clones share the same open files.
+#[derive(Clone)]
+pub struct Map(hash::SharedReader);
+
+/// The name `Map` had when `Writer::finalize` was added; both name the same type.
+pub type Database = Map;
+
+/// A difference between the live pairs of two databases, as found by `diff`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Diff {
+    /// A key only live in the second database, with its value.
+    Added(bytes::BytesMut, bytes::BytesMut),
+    /// A key only live in the first database, with its value.
+    Removed(bytes::BytesMut, bytes::BytesMut),
+    /// A key live in both databases with different values, with the old and the new value.
+    Changed(bytes::BytesMut, bytes::BytesMut, bytes::BytesMut),
+}
+
+/// Writes the log of a `Map`, building its hash index on `close`.
+pub struct Writer(hash::Builder);
+
+/// Rewrites the database at `src_base` into `dst_base`, keeping only the live value of each key.
+pub fn compact<P1, P2>(
+    src_base: P1,
+    dst_base: P2,
+    compression_type: log::CompressionType,
+    compression_block_size: u32,
+) -> error::Result<()>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+{
+    filter_into(
+        src_base,
+        dst_base,
+        compression_type,
+        compression_block_size,
+        |_, _| true,
+    )
+}
+
+/// Rewrites the database at `src_base` into `dst_base` like `compact`, keeping only the live
+/// key/value pairs for which `predicate` returns `true`.
+pub fn filter_into<P1, P2, F>(
+    src_base: P1,
+    dst_base: P2,
+    compression_type: log::CompressionType,
+    compression_block_size: u32,
+    mut predicate: F,
+) -> error::Result<()>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+    F: FnMut(&[u8], &[u8]) -> bool,
+{
+    let src = Map::open(src_base)?;
+    let mut dst = Map::create(dst_base, compression_type, compression_block_size)?;
+
+    for pair in src.iter()? {
+        let (key, value) = pair?;
+        if predicate(&key, &value) {
+            dst.put(&key, &value)?;
+        }
+    }
+
+    dst.close()?;
+
+    Ok(())
+}
+
+/// Merges the databases at `inputs` into `output_base`.  Each key gets its live value from the last
+/// input that has one, unless a later input deleted it.  Keeps the set of seen keys in memory.
+pub fn merge<P1, P2>(
+    inputs: &[P1],
+    output_base: P2,
+    compression_type: log::CompressionType,
+    compression_block_size: u32,
+) -> error::Result<()>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+{
+    let mut output = Map::create(output_base, compression_type, compression_block_size)?;
+    let mut seen = collections::HashSet::new();
+
+    for input in inputs.iter().rev() {
+        let input = Map::open(input)?;
+
+        for pair in input.iter()? {
+            let (key, value) = pair?;
+            if seen.insert(key.to_vec()) {
+                output.put(&key, &value)?;
+            }
+        }
+
+        // The hash index does not record deletes, so look for them in the log.
+        for entry in input.hash_reader().log_reader().entries()? {
+            let entry = entry?;
+            if entry.entry_type == log::EntryType::Delete && !input.contains_key(&entry.key)? {
+                seen.insert(entry.key.to_vec());
+            }
+        }
+    }
+
+    output.close()?;
+
+    Ok(())
+}
+
+/// Rewrites the database at `src_base` into `dst_base` with a different compression, then builds
+/// its hash index.  Unlike `compact`, every entry of the log is copied, including deletes and
+/// overwritten values, so only the framing of the log changes.
+pub fn transcode<P1, P2>(
+    src_base: P1,
+    dst_base: P2,
+    compression_type: log::CompressionType,
+    compression_block_size: u32,
+) -> error::Result<()>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+{
+    let src = log::Reader::open(paths(src_base.as_ref()).0)?;
+    let mut dst = Map::create(dst_base, compression_type, compression_block_size)?;
+
+    for entry in src.entries()? {
+        let entry = entry?;
+        match entry.entry_type {
+            log::EntryType::Put => dst.put(&entry.key, &entry.value)?,
+            log::EntryType::Delete => dst.delete(&entry.key)?,
+        }
+    }
+
+    dst.finalize()
+}
+
+/// Compares the live pairs of the databases at `base_a` and `base_b`, returning the differences
+/// sorted by key.
+///
+/// Rather than loading and sorting both databases, each live key of one is looked up in the
+/// index of the other, so only the differences are held in memory.
+pub fn diff<P1, P2>(base_a: P1, base_b: P2) -> error::Result<Vec<Diff>>
+where
+    P1: AsRef<path::Path>,
+    P2: AsRef<path::Path>,
+{
+    let a = Map::open(base_a)?;
+    let b = Map::open(base_b)?;
+    let mut diffs = Vec::new();
+
+    for pair in a.iter()? {
+        let (key, old) = pair?;
+        match b.get(&key)? {
+            None => diffs.push(Diff::Removed(key, old)),
+            Some(new) if new != old => diffs.push(Diff::Changed(key, old, new)),
+            Some(_) => {}
+        }
+    }
+
+    for pair in b.iter()? {
+        let (key, new) = pair?;
+        if !a.contains_key(&key)? {
+            diffs.push(Diff::Added(key, new));
+        }
+    }
+
+    diffs.sort_unstable_by(|x, y| x.key().cmp(y.key()));
+
+    Ok(diffs)
+}
+
+// Appends the extensions instead of using `with_extension`, which would replace anything after the
+// last dot of bases like `daily.2024-01-01`.
+fn paths(base: &path::Path) -> (path::PathBuf, path::PathBuf) {
+    let with = |extension| {
+        let mut path = base.as_os_str().to_owned();
+        path.push(extension);
+        path::PathBuf::from(path)
+    };
+
+    (with(".spl"), with(".spi"))
+}
+
+impl Diff {
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Diff::Added(key, _) | Diff::Removed(key, _) | Diff::Changed(key, _, _) => key,
+        }
+    }
+}
+
+impl Map {
+    pub fn open<P>(base: P) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        let (log_path, hash_path) = paths(base.as_ref());
+
+        Ok(Map(hash::Reader::open(hash_path, log_path)?.into()))
+    }
+
+    pub fn create<P>(
+        base: P,
+        compression_type: log::CompressionType,
+        compression_block_size: u32,
+    ) -> error::Result<Writer>
+    where
+        P: AsRef<path::Path>,
+    {
+        let (log_path, hash_path) = paths(base.as_ref());
+        let config = log::WriterConfig {
+            compression: compression_type,
+            block_size: compression_block_size,
+        };
+
+        Ok(Writer(hash::Builder::create(hash_path, log_path, config)?))
+    }
+
+    /// Opens a snapshot of the database that can be cloned and shared between threads.  It keeps
+    /// reading the files as they were when opened, even while a writer from `append` adds to them.
+    /// This is the same as `open`, since every `Map` is such a snapshot.
+    pub fn open_readonly<P>(base: P) -> error::Result<Self>
+    where
+        P: AsRef<path::Path>,
+    {
+        Map::open(base)
+    }
+
+    /// Opens an existing database for adding entries to the end of its log.  Its index is only
+    /// replaced on `Writer::finalize` or `Writer::close`.
+    pub fn append<P>(base: P) -> error::Result<Writer>
+    where
+        P: AsRef<path::Path>,
+    {
+        let (log_path, hash_path) = paths(base.as_ref());
+
+        Ok(Writer(hash::Builder::append(hash_path, log_path)?))
+    }
+
+    pub fn hash_reader(&self) -> &hash::Reader {
+        self.0.reader()
+    }
+
+    pub fn get(&self, key: &[u8]) -> error::Result<Option<bytes::BytesMut>> {
+        self.0.get(key)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> error::Result<bool> {
+        self.0.contains_key(key)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.0.reader().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.reader().is_empty()
+    }
+
+    pub fn iter(&self) -> error::Result<hash::Iter<'_>> {
+        self.0.iter()
+    }
+}
+
+impl Writer {
+    pub fn log_writer(&mut self) -> &mut log::Writer {
+        self.0.log_writer()
+    }
+
+    /// Sets the size of the hashes in the index written on `finalize` or `close`.  Defaults to
+    /// `HashSize::Auto`, which only picks 64-bit hashes once the index has room for 2^23 entries
+    /// or replaces a 64-bit index; force `HashSize::Bits64` to avoid relying on that.
+    pub fn set_hash_size(&mut self, hash_size: hash::HashSize) {
+        self.0.set_hash_size(hash_size);
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> error::Result<()> {
+        self.0.put(key, value)
+    }
+
+    pub fn delete(&mut self, key: &[u8]) -> error::Result<()> {
+        self.0.delete(key)
+    }
+
+    pub fn flush(&mut self) -> error::Result<()> {
+        self.0.log_writer().flush()
+    }
+
+    /// Closes the log and writes the hash index for it.
+    ///
+    /// The index is written with `hash::write_atomic`, so readers never see a partially written
+    /// index.
+    pub fn finalize(self) -> error::Result<()> {
+        self.0.finalize()
+    }
+
+    /// Closes the log, writes the hash index for it and opens the resulting `Map`.
+    pub fn close(self) -> error::Result<Map> {
+        Ok(Map(self.0.finish()?.into()))
+    }
+}
@@ -1,9 +1,8 @@
 use std::ffi;
-use std::io;
 use std::os;
 use std::path;
+use std::str;
 
-use libc;
 use sparkey_sys::*;
 
 use crate::error;
@@ -22,65 +21,51 @@ where
         Err(e) => Err(error::Error::PathContainsNul {
             path: path.to_path_buf(),
             position: e.nul_position(),
+            cause: e,
         }),
     }
 }
 
-pub fn handle(returncode: returncode) -> error::Result<()> {
-    use crate::error::Error::*;
-    use sparkey_sys::returncode::*;
+/// Converts `value` for a C API taking an `int`, failing with `TooLarge` instead of wrapping.
+pub fn to_c_int(what: &'static str, value: u32) -> error::Result<os::raw::c_int> {
+    use std::convert::TryFrom;
 
-    fn raw(raw: os::raw::c_int) -> error::Error {
-        error::Error::IO(io::Error::from_raw_os_error(raw))
-    }
+    os::raw::c_int::try_from(value).map_err(|_| error::Error::TooLarge {
+        what,
+        value: u64::from(value),
+        max: os::raw::c_int::MAX as u64,
+    })
+}
 
+pub fn handle(returncode: returncode) -> error::Result<()> {
     match returncode {
-        SUCCESS => Ok(()),
-
-        INTERNAL_ERROR => Err(Internal),
-
-        FILE_NOT_FOUND => Err(raw(libc::ENOENT)),
-        PERMISSION_DENIED => Err(raw(libc::EACCES)),
-        TOO_MANY_OPEN_FILES => Err(raw(libc::ENFILE)),
-        FILE_TOO_LARGE => Err(raw(libc::EOVERFLOW)),
-        FILE_ALREADY_EXISTS => Err(raw(libc::EEXIST)),
-        FILE_BUSY => Err(raw(libc::EBUSY)),
-        FILE_IS_DIRECTORY => Err(raw(libc::EISDIR)),
-        FILE_SIZE_EXCEEDED => Err(raw(libc::EFBIG)),
-        FILE_CLOSED => Err(raw(libc::EBADF)),
-        OUT_OF_DISK => Err(raw(libc::ENOSPC)),
-        UNEXPECTED_EOF => Err(UnexpectedEof),
-        MMAP_FAILED => Err(MmapFailed),
-
-        WRONG_LOG_MAGIC_NUMBER => Err(WrongLogMagicNumber),
-        WRONG_LOG_MAJOR_VERSION => Err(WrongLogMajorVersion),
-        UNSUPPORTED_LOG_MINOR_VERSION => Err(UnsupportedLogMinorVersion),
-        LOG_TOO_SMALL => Err(LogTooSmall),
-        LOG_CLOSED => Err(LogClosed),
-        LOG_ITERATOR_INACTIVE => Err(LogIteratorInactive),
-        LOG_ITERATOR_MISMATCH => Err(LogIteratorMismatch),
-        LOG_ITERATOR_CLOSED => Err(LogIteratorClosed),
-        LOG_HEADER_CORRUPT => Err(LogHeaderCorrupt),
-        INVALID_COMPRESSION_BLOCK_SIZE => Err(InvalidCompressionBlockSize),
-        INVALID_COMPRESSION_TYPE => Err(InvalidCompressionType),
-
-        WRONG_HASH_MAGIC_NUMBER => Err(WrongHashMagicNumber),
-        WRONG_HASH_MAJOR_VERSION => Err(WrongHashMajorVersion),
-        UNSUPPORTED_HASH_MINOR_VERSION => Err(UnsupportedHashMinorVersion),
-        HASH_TOO_SMALL => Err(HashTooSmall),
-        HASH_CLOSED => Err(HashClosed),
-        FILE_IDENTIFIER_MISMATCH => Err(FileIdentifierMismatch),
-        HASH_HEADER_CORRUPT => Err(HashHeaderCorrupt),
-        HASH_SIZE_INVALID => Err(HashSizeInvalid),
+        returncode::SUCCESS => Ok(()),
+        code => Err(code.into()),
     }
 }
 
-pub fn read_key(iter: *mut logiter, reader: *mut logreader) -> error::Result<bytes::BytesMut> {
+/// Checks that `bytes` are UTF-8, failing with `InvalidUtf8` naming `field` otherwise.
+pub fn to_str<'a>(field: &'static str, bytes: &'a [u8]) -> error::Result<&'a str> {
+    str::from_utf8(bytes).map_err(|_| error::Error::InvalidUtf8 {
+        field,
+        lossy: String::from_utf8_lossy(bytes).into_owned(),
+    })
+}
+
+/// Reads the current key into `buf`, replacing its contents and reusing its allocation if it is
+/// large enough.
+pub fn read_key_into(
+    iter: *mut logiter,
+    reader: *mut logreader,
+    mut buf: bytes::BytesMut,
+) -> error::Result<bytes::BytesMut> {
     use std::convert::TryFrom;
 
     let expected_len = unsafe { logiter_keylen(iter) };
     let mut actual_len = 0;
-    let mut buf = bytes::BytesMut::with_capacity(usize::try_from(expected_len).unwrap());
+
+    buf.clear();
+    buf.reserve(usize::try_from(expected_len).unwrap());
 
     unsafe {
         handle(logiter_fill_key(
@@ -98,11 +83,23 @@ pub fn read_key(iter: *mut logiter, reader: *mut logreader) -> error::Result<byt
 }
 
 pub fn read_value(iter: *mut logiter, reader: *mut logreader) -> error::Result<bytes::BytesMut> {
+    read_value_into(iter, reader, bytes::BytesMut::new())
+}
+
+/// Reads the current value into `buf`, replacing its contents and reusing its allocation if it is
+/// large enough.
+pub fn read_value_into(
+    iter: *mut logiter,
+    reader: *mut logreader,
+    mut buf: bytes::BytesMut,
+) -> error::Result<bytes::BytesMut> {
     use std::convert::TryFrom;
 
     let expected_len = unsafe { logiter_valuelen(iter) };
     let mut actual_len = 0;
-    let mut buf = bytes::BytesMut::with_capacity(usize::try_from(expected_len).unwrap());
+
+    buf.clear();
+    buf.reserve(usize::try_from(expected_len).unwrap());
 
     unsafe {
         handle(logiter_fill_value(
@@ -118,3 +115,394 @@ pub fn read_value(iter: *mut logiter, reader: *mut logreader) -> error::Result<b
 
     Ok(buf)
 }
+
+/// Reads the current key into `buf`, replacing its contents and reusing its allocation.
+pub fn fill_key(
+    iter: *mut logiter,
+    reader: *mut logreader,
+    buf: &mut Vec<u8>,
+) -> error::Result<()> {
+    let len = unsafe { logiter_keylen(iter) };
+
+    fill(buf, len, |ptr, actual_len| unsafe {
+        logiter_fill_key(iter, reader, len, ptr, actual_len)
+    })
+}
+
+/// Reads the current value into `buf`, replacing its contents and reusing its allocation.
+pub fn fill_value(
+    iter: *mut logiter,
+    reader: *mut logreader,
+    buf: &mut Vec<u8>,
+) -> error::Result<()> {
+    let len = unsafe { logiter_valuelen(iter) };
+
+    fill(buf, len, |ptr, actual_len| unsafe {
+        logiter_fill_value(iter, reader, len, ptr, actual_len)
+    })
+}
+
+fn fill<F>(buf: &mut Vec<u8>, expected_len: u64, f: F) -> error::Result<()>
+where
+    F: FnOnce(*mut u8, &mut u64) -> returncode,
+{
+    use std::convert::TryFrom;
+
+    let mut actual_len = 0;
+
+    buf.clear();
+    buf.reserve(usize::try_from(expected_len).unwrap());
+    handle(f(buf.as_mut_ptr(), &mut actual_len))?;
+    unsafe { buf.set_len(usize::try_from(actual_len).unwrap()) };
+    if actual_len != expected_len {
+        return Err(error::Error::UnexpectedEof);
+    }
+
+    Ok(())
+}
+
+/// The SplitMix64 generator: small, fast and good enough for sampling, but not for anything that
+/// needs unpredictable numbers.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`, which must not be 0.  Slightly biased unless `bound` is a power
+    /// of two, which doesn't matter for the bounds used here.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(bound)) >> 64) as u64
+    }
+}
+
+/// Reading and writing CSV records as described in RFC 4180.
+pub mod csv {
+    use std::io;
+    use std::mem;
+
+    use crate::error;
+
+    /// Writes `fields` as one record, quoting fields that need it and ending it with CRLF.
+    pub fn write_record<W>(out: &mut W, fields: &[&str]) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            if field.contains(&[',', '"', '\r', '\n'][..]) {
+                write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                out.write_all(field.as_bytes())?;
+            }
+        }
+        out.write_all(b"\r\n")
+    }
+
+    /// Reads the next record, returning the line it started on and its fields, or `None` at the
+    /// end of `input`.  Quoted fields may span lines; `line` counts the lines read so far.
+    pub fn read_record<R>(
+        input: &mut R,
+        line: &mut u64,
+    ) -> error::Result<Option<(u64, Vec<String>)>>
+    where
+        R: io::BufRead,
+    {
+        let mut text = String::new();
+        if read_line(input, &mut text, *line + 1)? == 0 {
+            return Ok(None);
+        }
+        *line += 1;
+
+        let start = *line;
+        let invalid = |reason: &str| error::Error::InvalidRecord {
+            line: start,
+            reason: reason.to_owned(),
+        };
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut was_quoted = false;
+
+        loop {
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => quoted = false,
+                    (true, c) => field.push(c),
+                    (false, '"') if field.is_empty() && !was_quoted => {
+                        quoted = true;
+                        was_quoted = true;
+                    }
+                    (false, '"') => return Err(invalid("unexpected `\"` in an unquoted field")),
+                    (false, ',') => {
+                        fields.push(mem::take(&mut field));
+                        was_quoted = false;
+                    }
+                    (false, '\r') if chars.peek() == Some(&'\n') => {}
+                    (false, '\n') => {}
+                    (false, _) if was_quoted => {
+                        return Err(invalid("unexpected characters after a quoted field"))
+                    }
+                    (false, c) => field.push(c),
+                }
+            }
+
+            if !quoted {
+                break;
+            }
+
+            text.clear();
+            if read_line(input, &mut text, *line + 1)? == 0 {
+                return Err(invalid("unterminated quoted field"));
+            }
+            *line += 1;
+        }
+
+        fields.push(field);
+
+        Ok(Some((start, fields)))
+    }
+
+    fn read_line<R>(input: &mut R, text: &mut String, line: u64) -> error::Result<usize>
+    where
+        R: io::BufRead,
+    {
+        input.read_line(text).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => error::Error::InvalidRecord {
+                line,
+                reason: "not valid UTF-8".to_owned(),
+            },
+            _ => error::Error::IO(e),
+        })
+    }
+}
+
+/// Values made of several parts, each prefixed with its length as a 4-byte little-endian
+/// integer.
+pub mod framed {
+    use std::convert::TryFrom;
+
+    use crate::error;
+
+    const PREFIX_LEN: usize = 4;
+
+    /// Concatenates `parts`, each prefixed with its length.
+    pub fn encode(parts: &[&[u8]]) -> error::Result<Vec<u8>> {
+        let len = parts.iter().map(|part| PREFIX_LEN + part.len()).sum();
+        let mut buf = Vec::with_capacity(len);
+
+        for part in parts {
+            let part_len = u32::try_from(part.len()).map_err(|_| error::Error::TooLarge {
+                what: "framed part length",
+                value: part.len() as u64,
+                max: u64::from(u32::MAX),
+            })?;
+            buf.extend_from_slice(&part_len.to_le_bytes());
+            buf.extend_from_slice(part);
+        }
+
+        Ok(buf)
+    }
+
+    /// Splits a value written by `encode` back into its parts.
+    pub fn decode(mut buf: &[u8]) -> error::Result<Vec<&[u8]>> {
+        let total = buf.len();
+        let invalid = |buf: &[u8], reason| error::Error::InvalidFrame {
+            position: total - buf.len(),
+            reason,
+        };
+        let mut parts = Vec::new();
+
+        while !buf.is_empty() {
+            if buf.len() < PREFIX_LEN {
+                return Err(invalid(buf, "truncated length prefix"));
+            }
+            let mut prefix = [0; PREFIX_LEN];
+            prefix.copy_from_slice(&buf[..PREFIX_LEN]);
+            let len = u32::from_le_bytes(prefix) as usize;
+
+            let rest = &buf[PREFIX_LEN..];
+            if rest.len() < len {
+                return Err(invalid(buf, "part extends past the end of the value"));
+            }
+            parts.push(&rest[..len]);
+            buf = &rest[len..];
+        }
+
+        Ok(parts)
+    }
+}
+
+/// Serde support for keys and values.
+///
+/// Keys and values are arbitrary bytes, so in human-readable formats (such as JSON) they are
+/// encoded as standard, padded base64 strings; binary formats get raw byte strings.
+#[cfg(feature = "serde")]
+pub mod serde_bytes {
+    use std::fmt;
+
+    pub struct Ref<'a>(pub &'a [u8]);
+
+    pub struct Owned(pub bytes::BytesMut);
+
+    struct Visitor;
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<bytes::BytesMut, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_byte_buf(Visitor)
+        }
+    }
+
+    impl<'a> serde::Serialize for Ref<'a> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serialize(self.0, serializer)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for Owned {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserialize(deserializer).map(Owned)
+        }
+    }
+
+    impl<'de> serde::de::Visitor<'de> for Visitor {
+        type Value = bytes::BytesMut;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a base64 string or a byte string")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            base64::decode(v)
+                .map(|v| v.as_slice().into())
+                .map_err(E::custom)
+        }
+
+        fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(v.into())
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut buf = bytes::BytesMut::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element::<u8>()? {
+                buf.extend_from_slice(&[b]);
+            }
+            Ok(buf)
+        }
+    }
+}
+
+/// Newline-delimited JSON records, one per entry; see `log::NdjsonEncoding` for the format.
+#[cfg(feature = "ndjson")]
+pub mod ndjson {
+    use crate::log;
+
+    #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+    pub struct Record {
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        pub entry_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub key_utf8: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub value: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub value_utf8: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub delete: Option<bool>,
+    }
+
+    /// Encodes `bytes` as `(base64, utf8)`, only one of which is set.
+    pub fn encode(bytes: &[u8], encoding: log::NdjsonEncoding) -> (Option<String>, Option<String>) {
+        match (encoding, std::str::from_utf8(bytes)) {
+            (log::NdjsonEncoding::Utf8IfValid, Ok(s)) => (None, Some(s.to_owned())),
+            _ => (Some(base64::encode(bytes)), None),
+        }
+    }
+
+    /// Decodes a field encoded by `encode`, describing what's wrong if it can't.
+    pub fn decode(
+        name: &str,
+        base64: Option<String>,
+        utf8: Option<String>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        match (base64, utf8) {
+            (Some(_), Some(_)) => Err(format!("both `{}` and `{}_utf8` are set", name, name)),
+            (Some(s), None) => base64::decode(&s)
+                .map(Some)
+                .map_err(|e| format!("`{}` is not valid base64: {}", name, e)),
+            (None, Some(s)) => Ok(Some(s.into_bytes())),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Decodes a record into its entry type, key and value, describing what's wrong if it can't.
+    pub fn decode_record(record: Record) -> Result<(log::EntryType, Vec<u8>, Vec<u8>), String> {
+        let entry_type = match (record.entry_type.as_deref(), record.delete) {
+            (Some("put"), Some(true)) => return Err("a put can't have `delete` set".to_owned()),
+            (Some("put"), _) | (None, None) | (None, Some(false)) => log::EntryType::Put,
+            (Some("delete"), Some(false)) => {
+                return Err("a delete can't have `delete` unset".to_owned())
+            }
+            (Some("delete"), _) | (None, Some(true)) => log::EntryType::Delete,
+            (Some(other), _) => return Err(format!("unknown type {:?}", other)),
+        };
+        let key = decode("key", record.key, record.key_utf8)?.ok_or("missing `key`")?;
+        let value = decode("value", record.value, record.value_utf8)?;
+
+        match (entry_type, value) {
+            (log::EntryType::Put, Some(value)) => Ok((entry_type, key, value)),
+            (log::EntryType::Put, None) => Err("missing `value`".to_owned()),
+            (log::EntryType::Delete, None) => Ok((entry_type, key, Vec::new())),
+            (log::EntryType::Delete, Some(_)) => Err("a delete can't have a value".to_owned()),
+        }
+    }
+}